@@ -69,7 +69,7 @@ use turbopack_ecmascript::resolve::cjs_resolve;
 use crate::{
     dynamic_imports::{collect_chunk_group, collect_evaluated_chunk_group},
     font::create_font_manifest,
-    loadable_manifest::create_react_loadable_manifest,
+    loadable_manifest::{create_react_loadable_manifest, LoadableManifestOptions},
     module_graph::get_reduced_graphs_for_endpoint,
     nft_json::NftJsonAsset,
     paths::{
@@ -79,7 +79,7 @@ use crate::{
     project::Project,
     route::{AppPageRoute, Endpoint, Route, Routes, WrittenEndpoint},
     server_actions::create_server_actions_manifest,
-    webpack_stats::generate_webpack_stats,
+    webpack_stats::{dynamic_imports_webpack_stats, generate_webpack_stats},
 };
 
 #[turbo_tasks::value]
@@ -1310,8 +1310,15 @@ impl AppEndpoint {
                             )
                             .into(),
                         ),
+                        LoadableManifestOptions::default().cell(),
                     );
                     server_assets.extend(loadable_manifest_output.await?.iter().copied());
+                    let dynamic_imports_webpack_stats = dynamic_imports_webpack_stats(
+                        this.app_project.project(),
+                        dynamic_import_entries,
+                        node_root.join(format!("server/app{}", app_entry.original_name).into()),
+                    );
+                    server_assets.extend(dynamic_imports_webpack_stats.await?.iter().copied());
                 }
 
                 AppEndpointOutput::Edge {
@@ -1362,8 +1369,15 @@ impl AppEndpoint {
                             )
                             .into(),
                         ),
+                        LoadableManifestOptions::default().cell(),
                     );
                     server_assets.extend(loadable_manifest_output.await?.iter().copied());
+                    let dynamic_imports_webpack_stats = dynamic_imports_webpack_stats(
+                        this.app_project.project(),
+                        dynamic_import_entries,
+                        node_root.join(format!("server/app{}", app_entry.original_name).into()),
+                    );
+                    server_assets.extend(dynamic_imports_webpack_stats.await?.iter().copied());
                 }
 
                 if this
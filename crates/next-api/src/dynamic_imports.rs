@@ -1,27 +1,61 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap},
+    fmt::Display,
+    time::Instant,
+};
 
 use anyhow::{bail, Result};
 use futures::Future;
-use swc_core::ecma::{
-    ast::{CallExpr, Callee, Expr, Ident, Lit},
-    visit::{Visit, VisitWith},
+use next_core::{
+    next_client_reference::EcmascriptClientReferenceModule, next_manifests::LoadableRouter,
+};
+use serde::{Deserialize, Serialize};
+use swc_core::{
+    common::{
+        comments::{CommentKind, Comments},
+        BytePos, SourceMap, Span, Spanned,
+    },
+    ecma::{
+        ast::{
+            ArrowExpr, AwaitExpr, CallExpr, Callee, Class, Expr, ForOfStmt, Function, GetterProp,
+            Ident, ImportDecl, ImportSpecifier, KeyValueProp, Lit, MemberExpr, MemberProp,
+            ModuleExportName, ObjectLit, ParenExpr, Program, Prop, PropName, PropOrSpread, Script,
+            SetterProp, Tpl, TsAsExpr, TsInstantiation, TsNonNullExpr, TsSatisfiesExpr,
+            TsTypeAssertion,
+        },
+        visit::{Visit, VisitWith},
+    },
 };
 use turbo_rcstr::RcStr;
-use turbo_tasks::{FxIndexMap, ResolvedVc, TryFlatJoinIterExt, Value, Vc};
+use turbo_tasks::{
+    trace::TraceRawVcs, FxIndexMap, NonLocalValue, ResolvedVc, TaskInput, TryFlatJoinIterExt,
+    TryJoinIterExt, Value, ValueToString, Vc,
+};
+use turbo_tasks_fs::{FileContent, FileSystemPath};
+use turbo_tasks_hash::{encode_hex, hash_xxh3_hash64};
 use turbopack_core::{
+    asset::Asset,
     chunk::{
         availability_info::AvailabilityInfo, ChunkableModule, ChunkingContext, ChunkingContextExt,
-        EvaluatableAsset,
+        EvaluatableAsset, ModuleId,
     },
     context::AssetContext,
+    issue::{
+        Issue, IssueExt, IssueSeverity, IssueSource, IssueStage, OptionIssueSource,
+        OptionStyledString, StyledString,
+    },
     module::Module,
-    output::OutputAssets,
+    output::{OutputAsset, OutputAssets},
     reference_type::EcmaScriptModulesReferenceSubType,
     resolve::{origin::PlainResolveOrigin, parse::Request, pattern::Pattern},
+    source::Source,
+};
+use turbopack_ecmascript::{
+    parse::ParseResult, resolve::esm_resolve, EcmascriptModuleAsset, EcmascriptParsable,
 };
-use turbopack_ecmascript::{parse::ParseResult, resolve::esm_resolve, EcmascriptParsable};
 
-use crate::module_graph::SingleModuleGraph;
+use crate::module_graph::{SingleModuleGraph, SingleModuleGraphNode};
 
 async fn collect_chunk_group_inner<F, Fu>(
     dynamic_import_entries: &FxIndexMap<ResolvedVc<Box<dyn Module>>, DynamicImportedModules>,
@@ -31,14 +65,19 @@ where
     F: FnMut(Vc<Box<dyn ChunkableModule>>) -> Fu,
     Fu: Future<Output = Result<Vc<OutputAssets>>> + Send,
 {
-    let mut chunks_hash: HashMap<RcStr, ResolvedVc<OutputAssets>> = HashMap::new();
+    // Chunk groups are cached per dynamically imported module, as the same module can be imported
+    // by many origins. The availability info is the same for all of them within a call, and
+    // repeated calls with the same availability info are served by the memoization of
+    // `chunk_group_assets`.
+    let mut chunks_hash: HashMap<ResolvedVc<Box<dyn Module>>, ResolvedVc<OutputAssets>> =
+        HashMap::new();
     let mut dynamic_import_chunks = FxIndexMap::default();
 
     // Iterate over the collected import mappings, and create a chunk for each
     // dynamic import.
     for (origin_module, dynamic_imports) in dynamic_import_entries {
         for (imported_raw_str, imported_module) in dynamic_imports {
-            let chunk = if let Some(chunk) = chunks_hash.get(imported_raw_str) {
+            let chunk = if let Some(chunk) = chunks_hash.get(imported_module) {
                 *chunk
             } else {
                 let Some(module) =
@@ -53,8 +92,17 @@ where
                 // naive hash to have additional
                 // chunks in case if there are same modules being imported in different
                 // origins.
+                let start = Instant::now();
                 let chunk_group = build_chunk(*module).await?.to_resolved().await?;
-                chunks_hash.insert(imported_raw_str.clone(), chunk_group);
+                if tracing::enabled!(tracing::Level::DEBUG) {
+                    let duration = start.elapsed();
+                    tracing::debug!(
+                        module = %imported_module.ident().to_string().await?,
+                        duration_ms = duration.as_secs_f64() * 1000.0,
+                        "resolved next/dynamic chunk group"
+                    );
+                }
+                chunks_hash.insert(*imported_module, chunk_group);
                 chunk_group
             };
 
@@ -97,6 +145,120 @@ pub(crate) async fn collect_evaluated_chunk_group(
     .await
 }
 
+/// Adds the source map of every chunk of the dynamic imports next to it, so consumers of the
+/// manifest can locate the source maps of each dynamic chunk. Chunking contexts only reference the
+/// source maps of chunks when source maps are enabled, otherwise the chunks are unchanged.
+#[turbo_tasks::function]
+pub async fn with_chunk_source_maps(
+    dynamic_imported_chunks: Vc<DynamicImportedChunks>,
+) -> Result<Vc<DynamicImportedChunks>> {
+    let entries = dynamic_imported_chunks
+        .await?
+        .iter()
+        .map(|(origin, dynamic_imports)| async move {
+            let dynamic_imports = dynamic_imports
+                .iter()
+                .map(|(import, chunk_output)| async move {
+                    let assets = chunk_output
+                        .await?
+                        .iter()
+                        .map(|&chunk| async move {
+                            let chunk_path = &*chunk.ident().path().await?;
+                            let source_maps = chunk
+                                .references()
+                                .await?
+                                .iter()
+                                .map(|&reference| async move {
+                                    let path = &*reference.ident().path().await?;
+                                    Ok(is_source_map_of(&chunk_path.path, &path.path)
+                                        .then_some(reference))
+                                })
+                                .try_flat_join()
+                                .await?;
+                            Ok(std::iter::once(chunk)
+                                .chain(source_maps)
+                                .collect::<Vec<_>>())
+                        })
+                        .try_flat_join()
+                        .await?;
+                    Ok((import.clone(), ResolvedVc::cell(assets)))
+                })
+                .try_join()
+                .await?;
+            Ok((*origin, dynamic_imports))
+        })
+        .try_join()
+        .await?;
+
+    Ok(Vc::cell(entries.into_iter().collect()))
+}
+
+fn is_source_map_of(chunk_path: &str, path: &str) -> bool {
+    path.strip_suffix(".map") == Some(chunk_path)
+}
+
+/// The chunk item ids of a dynamically imported module, for runtime integrations which need to
+/// refer to the module by id.
+#[turbo_tasks::value(shared)]
+pub struct DynamicImportChunkItemIds {
+    pub module_id: ResolvedVc<ModuleId>,
+    /// The id of the chunk item loading the module's chunk group asynchronously. Only collected
+    /// when requested.
+    pub async_loader_id: Option<ResolvedVc<ModuleId>>,
+}
+
+#[turbo_tasks::value(transparent)]
+pub struct DynamicImportedChunkItemIds(
+    pub FxIndexMap<ResolvedVc<Box<dyn Module>>, Vec<(RcStr, ResolvedVc<DynamicImportChunkItemIds>)>>,
+);
+
+/// Collects the chunk item ids of the dynamic imports, complementing the chunks collected by
+/// [collect_chunk_group] for the same entries. The id of the async loader chunk item is only
+/// included with `include_async_loader_ids`.
+#[turbo_tasks::function]
+pub async fn collect_chunk_item_ids(
+    chunking_context: Vc<Box<dyn ChunkingContext>>,
+    dynamic_import_entries: Vc<DynamicImports>,
+    include_async_loader_ids: bool,
+) -> Result<Vc<DynamicImportedChunkItemIds>> {
+    let mut dynamic_import_ids = FxIndexMap::default();
+    for (origin_module, dynamic_imports) in dynamic_import_entries.await?.iter() {
+        for (imported_raw_str, imported_module) in dynamic_imports {
+            let Some(module) =
+                ResolvedVc::try_sidecast::<Box<dyn ChunkableModule>>(*imported_module).await?
+            else {
+                bail!("module must be evaluatable");
+            };
+
+            let async_loader_id = if include_async_loader_ids {
+                Some(
+                    chunking_context
+                        .async_loader_chunk_item_id(*module)
+                        .to_resolved()
+                        .await?,
+                )
+            } else {
+                None
+            };
+            let ids = DynamicImportChunkItemIds {
+                module_id: chunking_context
+                    .chunk_item_id_from_ident(module.ident())
+                    .to_resolved()
+                    .await?,
+                async_loader_id,
+            }
+            .resolved_cell();
+
+            dynamic_import_ids
+                .entry(*origin_module)
+                .or_insert_with(Vec::new)
+                .push((imported_raw_str.clone(), ids));
+        }
+    }
+
+    Ok(Vc::cell(dynamic_import_ids))
+}
+
 /// Returns a mapping of the dynamic imports for the module, if the import is
 /// wrapped in `next/dynamic`'s `dynamic()`. Refer [documentation](https://nextjs.org/docs/pages/building-your-application/optimizing/lazy-loading#with-named-exports) for the usecases.
 ///
@@ -124,31 +286,131 @@ pub async fn build_dynamic_imports_map_for_module(
     client_asset_context: Vc<Box<dyn AssetContext>>,
     server_module: ResolvedVc<Box<dyn Module>>,
 ) -> Result<Vc<OptionDynamicImportsMap>> {
+    // The source of a module is the output of the webpack loaders configured for it
+    // (`turbo.rules`), so `dynamic()` calls in modules compiled from other languages are parsed and
+    // collected like any other.
     let Some(ecmascript_asset) =
         ResolvedVc::try_sidecast::<Box<dyn EcmascriptParsable>>(server_module).await?
     else {
         return Ok(Vc::cell(None));
     };
 
+    // Most modules don't use next/dynamic, skip parsing and visiting them.
+    if let FileContent::Content(file) = &*server_module.content().file_content().await? {
+        if let Ok(code) = file.content().to_str() {
+            if !may_contain_dynamic_imports(&code) {
+                return Ok(Vc::cell(None));
+            }
+        }
+    }
+
     // https://github.com/vercel/next.js/pull/56389#discussion_r1349336374
     // don't emit specific error as we expect there's a parse error already reported
-    let ParseResult::Ok { program, .. } = &*ecmascript_asset.failsafe_parse().await? else {
+    let ParseResult::Ok {
+        program,
+        comments,
+        source_map,
+        ..
+    } = &*ecmascript_asset.failsafe_parse().await?
+    else {
         return Ok(Vc::cell(None));
     };
 
+    if skips_dynamic_collection(&**comments, program) {
+        return Ok(Vc::cell(None));
+    }
+
+    let module_path = server_module.ident().path().await?;
+    let is_test_file = is_test_file(&module_path.path);
+
     // Reading the Program AST, collect raw imported module str if it's wrapped in
     // dynamic()
-    let mut visitor = DynamicImportVisitor::new();
-    program.visit_with(&mut visitor);
+    //
+    // The visitor borrows the comments, which are not `Send`, so it must not be held across
+    // awaits.
+    let (
+        imports,
+        folded_import_sources,
+        lazy_imports,
+        import_source_spans,
+        missing_loadable_generated,
+        max_depth_exceeded,
+        numeric_chunk_names,
+    ) = {
+        let mut visitor = DynamicImportVisitor::new(is_test_file).with_comments(&**comments);
+        program.visit_with(&mut visitor);
+        if !visitor.test_import_sources.is_empty() {
+            tracing::debug!(
+                path = %module_path.path,
+                count = visitor.test_import_sources.len(),
+                "excluded next/dynamic imports of a test file"
+            );
+        }
+        (
+            visitor.import_sources,
+            visitor.folded_import_sources,
+            visitor.lazy_import_sources,
+            visitor.import_source_spans,
+            visitor.missing_loadable_generated,
+            visitor.max_depth_exceeded,
+            visitor.numeric_chunk_names,
+        )
+    };
+
+    // The call sites are highlighted in the source by the dev overlay, if it is known.
+    let module_source = module_source(server_module).await?;
+
+    for (chunk_name, span) in numeric_chunk_names {
+        let source = match module_source {
+            Some(source) => Some(issue_source(source, span).to_resolved().await?),
+            None => None,
+        };
+        NumericChunkNameIssue {
+            file_path: server_module.ident().path().to_resolved().await?,
+            call_site: call_site(source_map, span),
+            chunk_name,
+            source,
+        }
+        .resolved_cell()
+        .emit();
+    }
+
+    if missing_loadable_generated {
+        MissingLoadableGeneratedIssue {
+            file_path: server_module.ident().path().to_resolved().await?,
+        }
+        .resolved_cell()
+        .emit();
+    }
 
-    if visitor.import_sources.is_empty() {
+    if max_depth_exceeded {
+        DynamicImportMaxDepthIssue {
+            file_path: server_module.ident().path().to_resolved().await?,
+            max_depth: DynamicImportVisitor::DEFAULT_MAX_DEPTH,
+        }
+        .resolved_cell()
+        .emit();
+    }
+
+    if imports.is_empty() && lazy_imports.is_empty() {
         return Ok(Vc::cell(None));
     }
 
-    let mut import_sources = vec![];
-    for import in visitor.import_sources.drain(..) {
+    let import_source_spans = &import_source_spans;
+    let resolve = |(import_source, import): (RcStr, RcStr)| async move {
         // Using the given `Module` which is the origin of the dynamic import, trying to
         // resolve the module that is being imported.
+        //
+        // The `exports` conditions have to match the environment the chunks are loaded in, not
+        // the one of the origin module: the client asset context provides `browser` and
+        // `development`/`production` depending on the mode, and `esm_resolve` adds `import`.
+        // Likewise the `baseUrl` and `paths` of the `tsconfig.json`/`jsconfig.json` closest to
+        // the origin module are applied by the client resolve options, which enable
+        // TypeScript.
+        let issue_source = match (module_source, import_source_spans.get(&import)) {
+            (Some(source), Some(span)) => Some(issue_source(source, *span).to_resolved().await?),
+            _ => None,
+        };
         let dynamic_imported_resolved_module = *esm_resolve(
             Vc::upcast(PlainResolveOrigin::new(
                 client_asset_context,
@@ -157,80 +419,802 @@ pub async fn build_dynamic_imports_map_for_module(
             Request::parse(Value::new(Pattern::Constant(import.clone()))),
             Value::new(EcmaScriptModulesReferenceSubType::DynamicImport),
             false,
-            None,
+            issue_source,
         )
         .first_module()
         .await?;
 
-        if let Some(dynamic_imported_resolved_module) = dynamic_imported_resolved_module {
-            import_sources.push((import, dynamic_imported_resolved_module));
-        }
-    }
+        // An import which can't be resolved (e.g. while the file is still being written in
+        // watch mode) is not retried here: within this task the resolve result is
+        // cached and would not change. The resolution depends on the file system
+        // reads it did, so once the watcher reports the file, the resolution and
+        // this task are invalidated and recomputed.
+        let Some(dynamic_imported_resolved_module) = dynamic_imported_resolved_module else {
+            return Ok(None);
+        };
+        let module = client_module_for_dynamic_import(dynamic_imported_resolved_module).await?;
+        anyhow::Ok(Some((import_source, module)))
+    };
+    // The modules of the folded imports are listed under the import source of their `dynamic()`
+    // call, so their chunks end up in its manifest entry.
+    let import_sources = imports
+        .into_iter()
+        .map(|import| (import.clone(), import))
+        .chain(folded_import_sources)
+        .map(&resolve)
+        .try_flat_join()
+        .await?;
+    let lazy_import_sources = lazy_imports
+        .into_iter()
+        .map(|import| (import.clone(), import))
+        .map(&resolve)
+        .try_flat_join()
+        .await?;
 
     Ok(Vc::cell(Some(ResolvedVc::cell((
         server_module,
-        import_sources,
+        merge_lazy_imports(import_sources, lazy_import_sources),
     )))))
 }
 
+/// Merges the `React.lazy()` imports of a module into its `next/dynamic` ones. A module loaded both
+/// ways is kept under its `next/dynamic` id only, which is canonical as it is the one the
+/// next/dynamic transform injects, so the manifest doesn't list it under two ids.
+fn merge_lazy_imports<M: PartialEq>(
+    mut imports: Vec<(RcStr, M)>,
+    lazy_imports: Vec<(RcStr, M)>,
+) -> Vec<(RcStr, M)> {
+    for (import, module) in lazy_imports {
+        if !imports.iter().any(|(_, existing)| *existing == module) {
+            imports.push((import, module));
+        }
+    }
+    imports
+}
+
+/// The directive opting a module out of the collection, e.g. for vendored files calling a
+/// different `dynamic()` API.
+const SKIP_DYNAMIC_COLLECTION_DIRECTIVE: &str = "@next-skip-dynamic-collection";
+
+/// Whether the leading comments of the module contain [SKIP_DYNAMIC_COLLECTION_DIRECTIVE], as a
+/// line (`// @next-skip-dynamic-collection`) or block comment.
+fn skips_dynamic_collection(comments: &dyn Comments, program: &Program) -> bool {
+    comments
+        .get_leading(program.span().lo)
+        .is_some_and(|comments| {
+            comments
+                .iter()
+                .any(|comment| comment.text.trim() == SKIP_DYNAMIC_COLLECTION_DIRECTIVE)
+        })
+}
+
+/// A server component can dynamically import a client component, e.g.
+/// `dynamic(() => import('./ClientThing'), { ssr: false })`. When the import resolves to the client
+/// reference crossing the RSC boundary, the client module behind it is the one which has to be
+/// chunked and preloaded.
+///
+/// [EcmascriptClientReferenceModule] and `NextDynamicEntryModule` are distinct value types rather
+/// than traits, so a module is at most one of the two and the downcast can't hide the other.
+async fn client_module_for_dynamic_import(
+    module: ResolvedVc<Box<dyn Module>>,
+) -> Result<ResolvedVc<Box<dyn Module>>> {
+    if let Some(client_reference) =
+        ResolvedVc::try_downcast_type::<EcmascriptClientReferenceModule>(module).await?
+    {
+        return Ok(ResolvedVc::upcast(client_reference.await?.client_module));
+    }
+    Ok(module)
+}
+
+#[turbo_tasks::value(shared)]
+struct DynamicImportMaxDepthIssue {
+    file_path: ResolvedVc<FileSystemPath>,
+    max_depth: u8,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for DynamicImportMaxDepthIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> Vc<IssueSeverity> {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn stage(&self) -> Vc<IssueStage> {
+        IssueStage::Analysis.into()
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> Vc<StyledString> {
+        StyledString::Text("Calls nested too deeply to collect next/dynamic imports".into()).cell()
+    }
+
+    #[turbo_tasks::function]
+    fn file_path(&self) -> Vc<FileSystemPath> {
+        *self.file_path
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> Vc<OptionStyledString> {
+        Vc::cell(Some(
+            StyledString::Text(
+                format!(
+                    "Calls nested deeper than {} levels are not searched for next/dynamic \
+                     imports, their chunks won't be preloaded.",
+                    self.max_depth
+                )
+                .into(),
+            )
+            .resolved_cell(),
+        ))
+    }
+}
+
+/// A pre-scan of the source of a module, telling whether it can contain anything collected by
+/// [DynamicImportVisitor] into the manifest: an import of `next/dynamic` or a `React.lazy()` call.
+/// A false positive only costs the visit.
+fn may_contain_dynamic_imports(code: &str) -> bool {
+    code.contains("next/dynamic") || code.contains("lazy")
+}
+
+/// The source of `module` to point its issues at, if it is known.
+async fn module_source(
+    module: ResolvedVc<Box<dyn Module>>,
+) -> Result<Option<ResolvedVc<Box<dyn Source>>>> {
+    Ok(
+        match ResolvedVc::try_downcast_type::<EcmascriptModuleAsset>(module).await? {
+            Some(module) => Some(module.await?.source),
+            None => None,
+        },
+    )
+}
+
+/// The [IssueSource] of the whole `module`, for issues without a more precise location.
+async fn module_issue_source(
+    module: ResolvedVc<Box<dyn Module>>,
+) -> Result<Option<ResolvedVc<IssueSource>>> {
+    Ok(match module_source(module).await? {
+        Some(source) => Some(IssueSource::from_source_only(source).to_resolved().await?),
+        None => None,
+    })
+}
+
+fn issue_source(source: ResolvedVc<Box<dyn Source>>, span: Span) -> Vc<IssueSource> {
+    IssueSource::from_swc_offsets(source, span.lo.to_usize(), span.hi.to_usize())
+}
+
+/// Formats the `line:column` and the source of a call, for the description of an issue.
+fn call_site(source_map: &SourceMap, span: Span) -> RcStr {
+    let loc = source_map.lookup_char_pos(span.lo);
+    let snippet = source_map.span_to_snippet(span).unwrap_or_default();
+    format!("{}:{}: {snippet}", loc.line, loc.col.0 + 1).into()
+}
+
+#[turbo_tasks::value(shared)]
+struct NumericChunkNameIssue {
+    file_path: ResolvedVc<FileSystemPath>,
+    /// The `line:column` and the source of the offending `dynamic()` call.
+    call_site: RcStr,
+    chunk_name: RcStr,
+    source: Option<ResolvedVc<IssueSource>>,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for NumericChunkNameIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> Vc<IssueSeverity> {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn stage(&self) -> Vc<IssueStage> {
+        IssueStage::Analysis.into()
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> Vc<StyledString> {
+        StyledString::Text("next/dynamic import uses a numeric webpackChunkName".into()).cell()
+    }
+
+    #[turbo_tasks::function]
+    fn file_path(&self) -> Vc<FileSystemPath> {
+        *self.file_path
+    }
+
+    #[turbo_tasks::function]
+    fn source(&self) -> Vc<OptionIssueSource> {
+        Vc::cell(self.source)
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> Vc<OptionStyledString> {
+        Vc::cell(Some(
+            StyledString::Stack(vec![
+                StyledString::Code(self.call_site.clone()),
+                StyledString::Text(
+                    format!(
+                        "The chunk name \"{}\" looks like a numeric chunk id and can conflict \
+                         with the ids of other chunks when building with webpack. Use a \
+                         descriptive string name instead, e.g. `webpackChunkName: \"feature\"`. \
+                         Turbopack ignores the chunk name and names chunks by their contents.",
+                        self.chunk_name
+                    )
+                    .into(),
+                ),
+            ])
+            .resolved_cell(),
+        ))
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct MissingLoadableGeneratedIssue {
+    file_path: ResolvedVc<FileSystemPath>,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for MissingLoadableGeneratedIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> Vc<IssueSeverity> {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn stage(&self) -> Vc<IssueStage> {
+        IssueStage::Analysis.into()
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> Vc<StyledString> {
+        StyledString::Text("next/dynamic call without loadableGenerated".into()).cell()
+    }
+
+    #[turbo_tasks::function]
+    fn file_path(&self) -> Vc<FileSystemPath> {
+        *self.file_path
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> Vc<OptionStyledString> {
+        Vc::cell(Some(
+            StyledString::Text(
+                "The options of a dynamic() call don't contain loadableGenerated, so the \
+                 next/dynamic transform was not applied to this module. Its dynamic imports can't \
+                 be matched to the loadable manifest."
+                    .into(),
+            )
+            .resolved_cell(),
+        ))
+    }
+}
+
+/// Whether the path is a test file, i.e. `*.test.*`, `*.spec.*` or inside of `__tests__`. Test
+/// files don't contribute to the production manifests.
+fn is_test_file(path: &str) -> bool {
+    let mut segments = path.split('/');
+    let file_name = segments.next_back().unwrap_or_default();
+    if segments.any(|segment| segment == "__tests__") {
+        return true;
+    }
+    file_name
+        .rsplit_once('.')
+        .is_some_and(|(stem, _)| stem.ends_with(".test") || stem.ends_with(".spec"))
+}
+
 /// A visitor to check if there's import to `next/dynamic`, then collecting the
-/// import wrapped with dynamic() via CollectImportSourceVisitor.
-struct DynamicImportVisitor {
+/// import wrapped with dynamic() via CollectImportSourceVisitor. Imports wrapped with
+/// `React.lazy()` are collected separately into `lazy_import_sources`. In test files, all imports
+/// are collected into `test_import_sources` instead.
+struct DynamicImportVisitor<'a> {
+    /// The comments of the visited program, to read pragmas from.
+    comments: Option<&'a dyn Comments>,
+    pub jsx_pragmas: JsxPragmas,
     dynamic_ident: Option<Ident>,
+    /// The default import of `next/dynamic`, the only binding whose calls the next/dynamic
+    /// transform rewrites.
+    default_dynamic_ident: Option<Ident>,
+    /// The default or namespace import of `react`, for `React.lazy()`.
+    react_ident: Option<Ident>,
+    /// The named `lazy` import of `react`.
+    lazy_ident: Option<Ident>,
+    /// The remaining nesting depth of calls to descend into, bounding the recursion for
+    /// adversarial or malformed inputs.
+    max_depth: u8,
+    is_test_file: bool,
     pub import_sources: Vec<RcStr>,
+    /// The spans of the specifiers of the `import()`s of `import_sources`, to point the issues of
+    /// resolving them at.
+    pub import_source_spans: HashMap<RcStr, Span>,
+    /// The import sources of the other `import()`s of the `dynamic()` calls, which may be loaded
+    /// as well, with the import source of the call. The transform injects the id of the latter
+    /// only, so the chunks of the former are folded into its manifest entry, see
+    /// [CollectImportSourceVisitor::into_import_sources].
+    pub folded_import_sources: Vec<(RcStr, RcStr)>,
+    pub lazy_import_sources: Vec<RcStr>,
+    pub test_import_sources: Vec<RcStr>,
+    /// The `retry` options of the `dynamic()` calls, by import source.
+    pub retry_options: Vec<(RcStr, DynamicImportRetry)>,
+    /// Whether a `dynamic()` call which the next/dynamic transform rewrites lacks the
+    /// `loadableGenerated` option it injects, see [DynamicImportVisitor::is_transformed_callee].
+    pub missing_loadable_generated: bool,
+    /// Whether calls were skipped because they are nested deeper than `max_depth`.
+    pub max_depth_exceeded: bool,
+    /// The `dynamic()` calls naming the chunk of the imported module with a numeric
+    /// `webpackChunkName`, e.g. `import(/* webpackChunkName: "123" */ './x')`, with that name.
+    pub numeric_chunk_names: Vec<(RcStr, Span)>,
 }
 
-impl DynamicImportVisitor {
-    fn new() -> Self {
+/// The `@jsxRuntime` and `@jsxImportSource` pragmas of a module, e.g.
+/// `/* @jsxRuntime classic */`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct JsxPragmas {
+    pub runtime: Option<RcStr>,
+    pub import_source: Option<RcStr>,
+}
+
+impl JsxPragmas {
+    fn parse(&mut self, comment: &str) {
+        let mut words = comment
+            .split_whitespace()
+            .map(|word| word.trim_start_matches('*'));
+        while let Some(word) = words.next() {
+            let pragma = match word {
+                "@jsxRuntime" => &mut self.runtime,
+                "@jsxImportSource" => &mut self.import_source,
+                _ => continue,
+            };
+            if let Some(value) = words.next() {
+                *pragma = Some(value.into());
+            }
+        }
+    }
+
+    /// Whether JSX compiles to `React.createElement()`, which requires `React` in scope.
+    fn is_classic_runtime(&self) -> bool {
+        self.runtime.as_deref() == Some("classic")
+    }
+}
+
+impl<'a> DynamicImportVisitor<'a> {
+    const DEFAULT_MAX_DEPTH: u8 = 32;
+
+    fn new(is_test_file: bool) -> Self {
         Self {
+            comments: None,
+            jsx_pragmas: Default::default(),
+            is_test_file,
             import_sources: vec![],
+            import_source_spans: HashMap::new(),
+            folded_import_sources: vec![],
+            lazy_import_sources: vec![],
+            test_import_sources: vec![],
+            retry_options: vec![],
+            missing_loadable_generated: false,
             dynamic_ident: None,
+            default_dynamic_ident: None,
+            react_ident: None,
+            lazy_ident: None,
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+            max_depth_exceeded: false,
+            numeric_chunk_names: vec![],
+        }
+    }
+
+    #[cfg(test)]
+    fn with_max_depth(mut self, max_depth: u8) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    fn with_comments(mut self, comments: &'a dyn Comments) -> Self {
+        self.comments = Some(comments);
+        self
+    }
+
+    fn is_dynamic(&self, ident: &Ident) -> bool {
+        self.dynamic_ident
+            .as_ref()
+            .is_some_and(|dynamic_ident| ident.sym == dynamic_ident.sym)
+    }
+
+    /// Whether the next/dynamic transform rewrites calls of `callee`. It matches the default import
+    /// of `next/dynamic` called directly only, e.g. not `(dynamic as any)(...)`.
+    fn is_transformed_callee(&self, callee: &Expr) -> bool {
+        matches!((callee, &self.default_dynamic_ident), (Expr::Ident(ident), Some(default_ident))
+            if ident.sym == default_ident.sym)
+    }
+
+    fn detect_jsx_pragmas(&mut self, pos: BytePos) {
+        let Some(comments) = self.comments.and_then(|comments| comments.get_leading(pos)) else {
+            return;
+        };
+        for comment in comments {
+            if comment.kind == CommentKind::Block {
+                self.jsx_pragmas.parse(&comment.text);
+            }
+        }
+    }
+
+    /// Returns the `webpackChunkName` of the `import()` whose specifier starts at `pos`, read from
+    /// the comments preceding the specifier.
+    fn webpack_chunk_name(&self, pos: BytePos) -> Option<RcStr> {
+        let comments = self.comments?.get_leading(pos)?;
+        comments
+            .iter()
+            .filter(|comment| comment.kind == CommentKind::Block)
+            .find_map(|comment| webpack_chunk_name(&comment.text))
+    }
+
+    fn is_react_lazy(&self, callee: &Expr) -> bool {
+        match callee {
+            Expr::Ident(ident) => self
+                .lazy_ident
+                .as_ref()
+                .is_some_and(|lazy_ident| ident.sym == lazy_ident.sym),
+            Expr::Member(MemberExpr {
+                obj,
+                prop: MemberProp::Ident(prop),
+                ..
+            }) => {
+                prop.sym == *"lazy"
+                    && matches!(&**obj, Expr::Ident(obj) if self.is_react_namespace(obj))
+            }
+            _ => false,
+        }
+    }
+
+    fn is_react_namespace(&self, ident: &Ident) -> bool {
+        match &self.react_ident {
+            Some(react_ident) => ident.sym == react_ident.sym,
+            // The classic runtime requires `React` in scope, it might be provided as a global
+            // instead of being imported.
+            None => self.jsx_pragmas.is_classic_runtime() && ident.sym == *"React",
         }
     }
 }
 
-impl Visit for DynamicImportVisitor {
+impl Visit for DynamicImportVisitor<'_> {
+    fn visit_module(&mut self, module: &swc_core::ecma::ast::Module) {
+        self.detect_jsx_pragmas(module.span.lo);
+        module.visit_children_with(self);
+    }
+
+    fn visit_script(&mut self, script: &Script) {
+        self.detect_jsx_pragmas(script.span.lo);
+        script.visit_children_with(self);
+    }
+
     fn visit_import_decl(&mut self, decl: &swc_core::ecma::ast::ImportDecl) {
-        // find import decl from next/dynamic, i.e import dynamic from 'next/dynamic'
-        if decl.src.value == *"next/dynamic" {
-            if let Some(specifier) = decl.specifiers.first().and_then(|s| s.as_default()) {
-                self.dynamic_ident = Some(specifier.local.clone());
+        // find import decl from next/dynamic, i.e import dynamic from 'next/dynamic'. The default
+        // specifier is matched by its kind, as transforms don't necessarily keep it first, and
+        // type-only imports don't bind `dynamic` at runtime.
+        if decl.src.value == *"next/dynamic" && !decl.type_only {
+            for specifier in &decl.specifiers {
+                match specifier {
+                    ImportSpecifier::Default(specifier) => {
+                        self.dynamic_ident = Some(specifier.local.clone());
+                        self.default_dynamic_ident = Some(specifier.local.clone());
+                    }
+                    ImportSpecifier::Named(specifier) if !specifier.is_type_only => {
+                        if let Some(ModuleExportName::Ident(imported)) = &specifier.imported {
+                            if &*imported.sym == "default" {
+                                self.dynamic_ident = Some(specifier.local.clone());
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // find import decl from react, i.e import React, { lazy } from 'react'
+        if decl.src.value == *"react" {
+            for specifier in &decl.specifiers {
+                match specifier {
+                    ImportSpecifier::Default(specifier) => {
+                        self.react_ident = Some(specifier.local.clone());
+                    }
+                    ImportSpecifier::Namespace(specifier) => {
+                        self.react_ident = Some(specifier.local.clone());
+                    }
+                    ImportSpecifier::Named(specifier) => {
+                        let imported = match &specifier.imported {
+                            Some(ModuleExportName::Ident(ident)) => &ident.sym,
+                            Some(ModuleExportName::Str(str_)) => &str_.value,
+                            None => &specifier.local.sym,
+                        };
+                        if imported == "lazy" {
+                            self.lazy_ident = Some(specifier.local.clone());
+                        }
+                    }
+                }
             }
         }
     }
 
     fn visit_call_expr(&mut self, call_expr: &CallExpr) {
+        if self.max_depth == 0 {
+            self.max_depth_exceeded = true;
+            return;
+        }
+
         // Collect imports if the import call is wrapped in the call dynamic()
-        if let Callee::Expr(ident) = &call_expr.callee {
-            if let Expr::Ident(ident) = &**ident {
-                if let Some(dynamic_ident) = &self.dynamic_ident {
-                    if ident.sym == *dynamic_ident.sym {
-                        let mut collect_import_source_visitor = CollectImportSourceVisitor::new();
-                        call_expr.visit_children_with(&mut collect_import_source_visitor);
-
-                        if let Some(import_source) = collect_import_source_visitor.import_source {
-                            self.import_sources.push(import_source);
+        if let Callee::Expr(callee) = &call_expr.callee {
+            if let Some(ident) = callee_ident(callee) {
+                if self.is_dynamic(ident) {
+                    let mut collect_import_source_visitor = CollectImportSourceVisitor::new();
+                    call_expr.visit_children_with(&mut collect_import_source_visitor);
+                    let (import_source, extra_import_sources) =
+                        collect_import_source_visitor.into_import_sources();
+                    let import_source_span = import_source.as_ref().map(|(_, span)| *span);
+                    let import_source = import_source.map(|(import_source, _)| import_source);
+
+                    if let (Some(span), false) = (import_source_span, self.is_test_file) {
+                        if let Some(chunk_name) = self
+                            .webpack_chunk_name(span.lo)
+                            .filter(|name| name.bytes().all(|byte| byte.is_ascii_digit()))
+                        {
+                            self.numeric_chunk_names.push((chunk_name, call_expr.span));
+                        }
+                    }
+                    if let (Some(import_source), Some(span), false) =
+                        (&import_source, import_source_span, self.is_test_file)
+                    {
+                        self.import_source_spans
+                            .entry(import_source.clone())
+                            .or_insert(span);
+                    }
+                    if let (Some(import_source), Some(retry), false) =
+                        (&import_source, retry_option(call_expr), self.is_test_file)
+                    {
+                        self.retry_options.push((import_source.clone(), retry));
+                    }
+                    // Calls the transform doesn't rewrite, or whose import it can't capture, never
+                    // get the option.
+                    if self.is_transformed_callee(callee)
+                        && import_source.is_some()
+                        && !self.is_test_file
+                        && !has_loadable_generated(call_expr)
+                    {
+                        self.missing_loadable_generated = true;
+                    }
+                    let declared_modules = declared_modules(call_expr)
+                        .filter(|module| import_source.as_ref() != Some(module))
+                        .collect::<Vec<_>>();
+                    if self.is_test_file {
+                        self.test_import_sources.extend(
+                            extra_import_sources
+                                .into_iter()
+                                .map(|(extra_import_source, _)| extra_import_source),
+                        );
+                    } else if let Some(import_source) = &import_source {
+                        for (extra_import_source, span) in extra_import_sources {
+                            self.import_source_spans
+                                .entry(extra_import_source.clone())
+                                .or_insert(span);
+                            self.folded_import_sources
+                                .push((import_source.clone(), extra_import_source));
                         }
                     }
+                    let import_sources = if self.is_test_file {
+                        &mut self.test_import_sources
+                    } else {
+                        &mut self.import_sources
+                    };
+                    import_sources.extend(import_source);
+                    import_sources.extend(declared_modules);
                 }
             }
+
+            // Collect imports wrapped in React.lazy() separately, they are merged into the
+            // next/dynamic ones, see `merge_lazy_imports`.
+            if self.is_react_lazy(callee) {
+                let mut collect_import_source_visitor = CollectImportSourceVisitor::new();
+                call_expr.visit_children_with(&mut collect_import_source_visitor);
+                let import_sources = if self.is_test_file {
+                    &mut self.test_import_sources
+                } else {
+                    &mut self.lazy_import_sources
+                };
+                import_sources.extend(collect_import_source_visitor.import_source);
+            }
         }
 
+        // Also visits the arguments, so `dynamic()` calls nested in other calls such as
+        // `React.memo(dynamic(() => import('./X')))` are collected as well. JSX children and
+        // attributes (e.g. render props) are visited by the default traversal.
+        //
+        // The calls compiled from JSX elements don't count towards the depth, as their nesting
+        // follows the one of the markup rather than of the code.
+        if is_jsx_factory_call(call_expr) {
+            call_expr.visit_children_with(self);
+            return;
+        }
+        self.max_depth -= 1;
         call_expr.visit_children_with(self);
+        self.max_depth += 1;
+    }
+}
+
+/// Whether the call creates a JSX element, as compiled by the automatic (`_jsx()`, `_jsxs()`,
+/// `_jsxDEV()`) or the classic runtime (`React.createElement()`).
+fn is_jsx_factory_call(call_expr: &CallExpr) -> bool {
+    let Callee::Expr(callee) = &call_expr.callee else {
+        return false;
+    };
+    match &**callee {
+        Expr::Ident(ident) => matches!(
+            &*ident.sym,
+            "_jsx" | "_jsxs" | "_jsxDEV" | "jsx" | "jsxs" | "jsxDEV" | "createElement"
+        ),
+        Expr::Member(MemberExpr {
+            prop: MemberProp::Ident(prop),
+            ..
+        }) => prop.sym == *"createElement",
+        _ => false,
+    }
+}
+
+/// Returns the import sources declared explicitly via the `modules: ['./x']` option of a
+/// `dynamic()` call, as used by legacy `react-loadable` code.
+///
+/// Only the options object itself is checked, so the `modules` list inside `loadableGenerated`
+/// (which contains import ids, not sources) is not picked up.
+fn declared_modules(call_expr: &CallExpr) -> impl Iterator<Item = RcStr> + '_ {
+    call_expr
+        .args
+        .iter()
+        .filter_map(|arg| match &*arg.expr {
+            Expr::Object(options) => match object_lit_value(options, "modules") {
+                Some(Expr::Array(modules)) => Some(modules),
+                _ => None,
+            },
+            _ => None,
+        })
+        .flat_map(|modules| modules.elems.iter().flatten())
+        .filter_map(|module| match &*module.expr {
+            Expr::Lit(Lit::Str(str_)) if module.spread.is_none() => {
+                Some(str_.value.as_str().into())
+            }
+            _ => None,
+        })
+}
+
+/// The `retry` option of a `dynamic()` call, declaring how the runtime retries failed chunk loads,
+/// e.g. `dynamic(() => import('./x'), { retry: { maxAttempts: 3, retriable: true } })`.
+#[turbo_tasks::value(shared)]
+#[derive(Clone, Debug, Default)]
+pub struct DynamicImportRetry {
+    pub max_attempts: Option<u32>,
+    pub retriable: Option<bool>,
+}
+
+/// The identifier called, seeing through the TypeScript-only wrappers of it which don't change the
+/// value, e.g. `dynamic!(...)`, `(dynamic as typeof load)(...)` or the instantiation expression
+/// `(dynamic<Props>)(...)`.
+fn callee_ident(callee: &Expr) -> Option<&Ident> {
+    match callee {
+        Expr::Ident(ident) => Some(ident),
+        Expr::Paren(ParenExpr { expr, .. })
+        | Expr::TsNonNull(TsNonNullExpr { expr, .. })
+        | Expr::TsAs(TsAsExpr { expr, .. })
+        | Expr::TsSatisfies(TsSatisfiesExpr { expr, .. })
+        | Expr::TsTypeAssertion(TsTypeAssertion { expr, .. })
+        | Expr::TsInstantiation(TsInstantiation { expr, .. }) => callee_ident(expr),
+        _ => None,
     }
 }
 
+fn has_loadable_generated(call_expr: &CallExpr) -> bool {
+    call_expr.args.iter().any(|arg| {
+        matches!(&*arg.expr, Expr::Object(options)
+            if object_lit_value(options, "loadableGenerated").is_some())
+    })
+}
+
+fn retry_option(call_expr: &CallExpr) -> Option<DynamicImportRetry> {
+    call_expr.args.iter().find_map(|arg| {
+        let Expr::Object(options) = &*arg.expr else {
+            return None;
+        };
+        let Some(Expr::Object(retry)) = object_lit_value(options, "retry") else {
+            return None;
+        };
+        Some(DynamicImportRetry {
+            max_attempts: match object_lit_value(retry, "maxAttempts") {
+                Some(Expr::Lit(Lit::Num(num))) => u32::try_from(num.value as i64).ok(),
+                _ => None,
+            },
+            retriable: match object_lit_value(retry, "retriable") {
+                Some(Expr::Lit(Lit::Bool(bool_))) => Some(bool_.value),
+                _ => None,
+            },
+        })
+    })
+}
+
+/// Returns the value of the `webpackChunkName` magic comment in the text of a comment, e.g. `hello`
+/// for `webpackChunkName: "hello"`, if it has one.
+fn webpack_chunk_name(comment: &str) -> Option<RcStr> {
+    let (_, value) = comment.split_once("webpackChunkName")?;
+    let value = value.trim_start().strip_prefix(':')?.trim_start();
+    let quote = value.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    let (name, _) = value[1..].split_once(quote)?;
+    (!name.is_empty()).then(|| name.into())
+}
+
+/// Returns the value of the `key` property of an object literal, e.g. of the options passed to
+/// `dynamic()`.
+fn object_lit_value<'a>(object: &'a ObjectLit, key: &str) -> Option<&'a Expr> {
+    object.props.iter().find_map(|prop| {
+        let PropOrSpread::Prop(prop) = prop else {
+            return None;
+        };
+        let Prop::KeyValue(KeyValueProp {
+            key: prop_key,
+            value,
+        }) = &**prop
+        else {
+            return None;
+        };
+        let is_key = match prop_key {
+            PropName::Ident(ident) => &*ident.sym == key,
+            PropName::Str(str_) => &*str_.value == key,
+            _ => false,
+        };
+        is_key.then_some(&**value)
+    })
+}
+
 /// A visitor to collect import source string from import('path/to/module')
 struct CollectImportSourceVisitor {
     import_source: Option<RcStr>,
+    /// The span of the specifier of the `import()` whose source was found, to read the magic
+    /// comments preceding it.
+    import_source_span: Option<Span>,
+    /// The import sources of the other `import()`s which may be loaded as well, with the spans of
+    /// their specifiers: those in arrow functions which are object property values, as in an
+    /// enum-keyed map whose entry is picked at runtime, and the elements of an array iterated over
+    /// with `for await`, e.g.
+    ///
+    /// ```js
+    /// ({ [Kind.A]: () => import('./a'), [Kind.B]: () => import('./b') })[kind]()
+    /// for await (const mod of [import('./a'), import('./b')]) {}
+    /// ```
+    extra_import_sources: Vec<(RcStr, Span)>,
 }
 
 impl CollectImportSourceVisitor {
     fn new() -> Self {
         Self {
             import_source: None,
+            import_source_span: None,
+            extra_import_sources: Vec::new(),
         }
     }
+
+    fn collect_extra_import_sources(&mut self, node: &impl VisitWith<Self>) {
+        let mut visitor = CollectImportSourceVisitor::new();
+        node.visit_with(&mut visitor);
+        self.extra_import_sources
+            .extend(visitor.import_source.zip(visitor.import_source_span));
+        self.extra_import_sources
+            .extend(visitor.extra_import_sources);
+    }
+
+    /// Splits the collected import sources into the one the next/dynamic transform generates the
+    /// id of the manifest entry from, the last `import()` in source order, and the others, whose
+    /// chunks are folded into that entry.
+    fn into_import_sources(self) -> (Option<(RcStr, Span)>, Vec<(RcStr, Span)>) {
+        let mut import_sources = self.extra_import_sources;
+        import_sources.extend(self.import_source.zip(self.import_source_span));
+        import_sources.sort_by_key(|(_, span)| span.lo);
+        let Some((import_source, span)) = import_sources.pop() else {
+            return (None, vec![]);
+        };
+        import_sources.retain(|(extra, _)| *extra != import_source);
+        (Some((import_source, span)), import_sources)
+    }
 }
 
 impl Visit for CollectImportSourceVisitor {
@@ -238,71 +1222,2575 @@ impl Visit for CollectImportSourceVisitor {
         // find import source from import('path/to/module')
         // [NOTE]: Turbopack does not support webpack-specific comment directives, i.e
         // import(/* webpackChunkName: 'hello1' */ '../../components/hello3')
-        // Renamed chunk in the comment will be ignored.
+        // Renamed chunk in the comment will be ignored, numeric ones are warned about.
         if let Callee::Import(_import) = call_expr.callee {
             if let Some(arg) = call_expr.args.first() {
-                if let Expr::Lit(Lit::Str(str_)) = &*arg.expr {
-                    self.import_source = Some(str_.value.as_str().into());
+                if let Some(import_source) = static_import_source(&arg.expr) {
+                    self.import_source = Some(import_source);
+                    self.import_source_span = Some(arg.expr.span());
                 }
             }
+            // Don't need to visit children, we expect import() won't have any
+            // nested calls as dynamic() should be statically analyzable import.
+            return;
         }
 
-        // Don't need to visit children, we expect import() won't have any
-        // nested calls as dynamic() should be statically analyzable import.
+        // The import() may be wrapped in other calls, e.g. `import('./x').then(mod => mod.X)` or
+        // an arrow function invoked immediately, `(() => import('./x'))()`, as found in minified
+        // code. Sequence expressions (`(0, import('./x'))`) are covered by the default traversal,
+        // the last import() found is the one kept.
+        call_expr.visit_children_with(self);
     }
-}
 
-pub type DynamicImportedModules = Vec<(RcStr, ResolvedVc<Box<dyn Module>>)>;
-pub type DynamicImportedOutputAssets = Vec<(RcStr, ResolvedVc<OutputAssets>)>;
+    fn visit_object_lit(&mut self, object: &ObjectLit) {
+        for prop in &object.props {
+            if let PropOrSpread::Prop(prop) = prop {
+                if let Prop::KeyValue(KeyValueProp { value, .. }) = &**prop {
+                    let mut value = &**value;
+                    while let Expr::Paren(paren) = value {
+                        value = &paren.expr;
+                    }
+                    if let Expr::Arrow(arrow) = value {
+                        self.collect_extra_import_sources(arrow);
+                        continue;
+                    }
+                }
+            }
+            prop.visit_with(self);
+        }
+    }
 
-/// A struct contains mapping for the dynamic imports to construct chunk per
-/// each individual module (Origin Module, Vec<(ImportSourceString, Module)>)
-#[turbo_tasks::value(transparent)]
-pub struct DynamicImportsMap(pub (ResolvedVc<Box<dyn Module>>, DynamicImportedModules));
+    fn visit_for_of_stmt(&mut self, for_of: &ForOfStmt) {
+        if let Expr::Array(array) = &*for_of.right {
+            for elem in array.elems.iter().flatten() {
+                self.collect_extra_import_sources(elem);
+            }
+            for_of.left.visit_with(self);
+            for_of.body.visit_with(self);
+            return;
+        }
+        for_of.visit_children_with(self);
+    }
+}
 
-/// An Option wrapper around [DynamicImportsMap].
-#[turbo_tasks::value(transparent)]
-pub struct OptionDynamicImportsMap(Option<ResolvedVc<DynamicImportsMap>>);
+/// The specifier of an `import()` as captured by the next/dynamic transform, which generates the
+/// id of the manifest entry from it: a string literal or a template without expressions, taken as
+/// written (`` `./x` ``). Anything else can't be analyzed statically.
+fn static_import_source(expr: &Expr) -> Option<RcStr> {
+    match expr {
+        Expr::Lit(Lit::Str(str_)) => Some(str_.value.as_str().into()),
+        Expr::Tpl(Tpl { exprs, quasis, .. }) if exprs.is_empty() => {
+            Some(quasis[0].raw.as_str().into())
+        }
+        _ => None,
+    }
+}
+
+/// A visitor to collect the sources of the `await import('path/to/module')` expressions at the top
+/// level of a module, i.e. outside of functions and classes, which top-level await allows. They
+/// aren't next/dynamic but are loaded before the module evaluates, so they can be preloaded.
+struct TopLevelAwaitImportVisitor {
+    import_sources: Vec<RcStr>,
+}
+
+impl Visit for TopLevelAwaitImportVisitor {
+    fn visit_await_expr(&mut self, await_expr: &AwaitExpr) {
+        let mut arg = &*await_expr.arg;
+        while let Expr::Paren(paren) = arg {
+            arg = &paren.expr;
+        }
+        if let Expr::Call(CallExpr {
+            callee: Callee::Import(_),
+            args,
+            ..
+        }) = arg
+        {
+            self.import_sources
+                .extend(args.first().and_then(|arg| static_import_source(&arg.expr)));
+            return;
+        }
+        await_expr.visit_children_with(self);
+    }
+
+    // Awaits in functions and classes don't run at the top level.
+    fn visit_function(&mut self, _: &Function) {}
+
+    fn visit_arrow_expr(&mut self, _: &ArrowExpr) {}
+
+    fn visit_class(&mut self, _: &Class) {}
+
+    fn visit_getter_prop(&mut self, _: &GetterProp) {}
+
+    fn visit_setter_prop(&mut self, _: &SetterProp) {}
+}
+
+/// The modules imported with `await import()` at the top level of a module, see
+/// [top_level_dynamic_imports]. They are kept apart from the next/dynamic imports and don't end up
+/// in the react-loadable manifest.
+#[turbo_tasks::value(transparent)]
+pub struct TopLevelDynamicImports(pub DynamicImportedModules);
+
+/// Collects the top-level `await import()` expressions of `server_module`, for teams preloading
+/// them. The imports are resolved like the next/dynamic ones, in the client asset context.
+#[turbo_tasks::function]
+pub async fn top_level_dynamic_imports(
+    client_asset_context: Vc<Box<dyn AssetContext>>,
+    server_module: ResolvedVc<Box<dyn Module>>,
+) -> Result<Vc<TopLevelDynamicImports>> {
+    let Some(ecmascript_asset) =
+        ResolvedVc::try_sidecast::<Box<dyn EcmascriptParsable>>(server_module).await?
+    else {
+        return Ok(Vc::cell(vec![]));
+    };
+    let ParseResult::Ok { program, .. } = &*ecmascript_asset.failsafe_parse().await? else {
+        return Ok(Vc::cell(vec![]));
+    };
+
+    let mut visitor = TopLevelAwaitImportVisitor {
+        import_sources: vec![],
+    };
+    program.visit_with(&mut visitor);
+
+    let imports = visitor
+        .import_sources
+        .into_iter()
+        .map(|import| async move {
+            let Some(module) = *esm_resolve(
+                Vc::upcast(PlainResolveOrigin::new(
+                    client_asset_context,
+                    server_module.ident().path(),
+                )),
+                Request::parse(Value::new(Pattern::Constant(import.clone()))),
+                Value::new(EcmaScriptModulesReferenceSubType::DynamicImport),
+                false,
+                None,
+            )
+            .first_module()
+            .await?
+            else {
+                return Ok(None);
+            };
+            Ok(Some((
+                import,
+                client_module_for_dynamic_import(module).await?,
+            )))
+        })
+        .try_flat_join()
+        .await?;
+    Ok(Vc::cell(imports))
+}
+
+pub type DynamicImportedModules = Vec<(RcStr, ResolvedVc<Box<dyn Module>>)>;
+pub type DynamicImportedOutputAssets = Vec<(RcStr, ResolvedVc<OutputAssets>)>;
+pub type DynamicImportedFiles = Vec<(RcStr, ResolvedVc<Box<dyn OutputAsset>>)>;
+
+/// A struct contains mapping for the dynamic imports to construct chunk per
+/// each individual module (Origin Module, Vec<(ImportSourceString, Module)>)
+#[turbo_tasks::value(transparent)]
+pub struct DynamicImportsMap(pub (ResolvedVc<Box<dyn Module>>, DynamicImportedModules));
+
+/// A fingerprint of a [DynamicImportsMap], which stays the same as long as the origin module, its
+/// import sources and the modules they resolve to do, regardless of their order. Consumers can
+/// compare it to decide whether work depending on the map has to run again.
+#[turbo_tasks::function]
+pub async fn dynamic_imports_map_fingerprint(map: Vc<DynamicImportsMap>) -> Result<Vc<RcStr>> {
+    let (origin, imports) = &*map.await?;
+    let origin = origin.ident().to_string().await?;
+    let imports = imports
+        .iter()
+        .map(|(import, module)| async move {
+            Ok((
+                import.clone(),
+                module.ident().to_string().await?.clone_value(),
+            ))
+        })
+        .try_join()
+        .await?;
+    Ok(Vc::cell(fingerprint(&origin, imports)))
+}
+
+fn fingerprint(origin: &RcStr, mut imports: Vec<(RcStr, RcStr)>) -> RcStr {
+    imports.sort();
+    encode_hex(hash_xxh3_hash64((origin, imports))).into()
+}
+
+/// An Option wrapper around [DynamicImportsMap].
+#[turbo_tasks::value(transparent)]
+pub struct OptionDynamicImportsMap(Option<ResolvedVc<DynamicImportsMap>>);
 
 #[turbo_tasks::value(transparent)]
 pub struct DynamicImportedChunks(
     pub FxIndexMap<ResolvedVc<Box<dyn Module>>, DynamicImportedOutputAssets>,
 );
 
+/// A key of the chunk groups of [DynamicImportedChunks], which stays the same as long as every
+/// dynamic import, by its id, loads the same chunks, regardless of the order the graph was walked
+/// in. Equivalent graphs share it, so work depending on the chunk groups can be cached by it.
+#[turbo_tasks::function]
+pub async fn dynamic_imported_chunks_fingerprint(
+    dynamic_imported_chunks: Vc<DynamicImportedChunks>,
+) -> Result<Vc<RcStr>> {
+    let chunk_groups = dynamic_imported_chunks
+        .await?
+        .iter()
+        .map(|(origin, dynamic_imports)| async move {
+            let origin_path = &*origin.ident().path().await?;
+            dynamic_imports
+                .iter()
+                .map(|(import, chunk_output)| async move {
+                    let chunk_ids = chunk_output
+                        .await?
+                        .iter()
+                        .map(|chunk| async move { Ok(chunk.ident().path().await?.path.clone()) })
+                        .try_join()
+                        .await?;
+                    Ok((dynamic_import_id(origin_path, import), chunk_ids))
+                })
+                .try_join()
+                .await
+        })
+        .try_flat_join()
+        .await?;
+    Ok(Vc::cell(chunk_groups_fingerprint(chunk_groups)))
+}
+
+/// Hashes the `(dynamic import id, chunk ids)` pairs sorted, keeping the chunk order of each pair,
+/// which the runtime loads them in.
+fn chunk_groups_fingerprint(mut chunk_groups: Vec<(RcStr, Vec<RcStr>)>) -> RcStr {
+    chunk_groups.sort();
+    encode_hex(hash_xxh3_hash64(chunk_groups)).into()
+}
+
+/// The [DynamicImportedChunks] resolved into the id of each dynamic import, in the form of
+/// `${origin} -> ${imported}`, and its files, as paths relative to a base path alongside the
+/// asset they point to. Files outside of the base path are omitted, unless a cross-origin base URL
+/// is given, see [resolve_dynamic_imported_chunk_files_cross_origin].
+#[turbo_tasks::value(transparent)]
+pub struct DynamicImportedChunkFiles(pub Vec<(RcStr, DynamicImportedFiles)>);
+
 /// "app/client.js [app-ssr] (ecmascript)" ->
 ///      [("./dynamic", "app/dynamic.js [app-client] (ecmascript)")])]
 #[turbo_tasks::value(transparent)]
 pub struct DynamicImports(pub FxIndexMap<ResolvedVc<Box<dyn Module>>, DynamicImportedModules>);
 
+/// The entrypoints reaching each module with dynamic imports, as the paths of the entry modules,
+/// e.g. "app/client.js [app-ssr] (ecmascript)" -> ["app/page.js", "app/other/page.js"].
+#[turbo_tasks::value(transparent)]
+pub struct DynamicImportScopes(pub FxIndexMap<ResolvedVc<Box<dyn Module>>, Vec<RcStr>>);
+
+/// Inverts the modules reached by each entrypoint into the entrypoints reaching each module, in
+/// order of the entrypoints and without duplicates.
+pub(crate) fn scopes_by_module<M: Copy + Eq + std::hash::Hash>(
+    reached_by_entry: impl IntoIterator<Item = (RcStr, Vec<M>)>,
+) -> FxIndexMap<M, Vec<RcStr>> {
+    let mut scopes: FxIndexMap<M, Vec<RcStr>> = FxIndexMap::default();
+    for (entry, modules) in reached_by_entry {
+        for module in modules {
+            let scope = scopes.entry(module).or_default();
+            if !scope.contains(&entry) {
+                scope.push(entry.clone());
+            }
+        }
+    }
+    scopes
+}
+
+/// How [merge_dynamic_imports] combines the dynamic imports of a module found in several graphs.
+#[derive(
+    Copy,
+    Clone,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    Hash,
+    Debug,
+    TaskInput,
+    TraceRawVcs,
+    NonLocalValue,
+)]
+pub enum DynamicImportsMergeStrategy {
+    /// Keep the dynamic imports of the module from the first graph listing it.
+    PreferFirst,
+    /// Keep the dynamic imports of the module from all graphs, in order and without duplicates.
+    Union,
+}
+
+/// Merges the dynamic imports collected from several module graphs, e.g. with [map_next_dynamic]
+/// per graph in multi-graph builds, into one. Modules are in order of first appearance.
 #[turbo_tasks::function]
-pub async fn map_next_dynamic(
-    graph: Vc<SingleModuleGraph>,
-    client_asset_context: Vc<Box<dyn AssetContext>>,
+pub async fn merge_dynamic_imports(
+    dynamic_imports: Vec<Vc<DynamicImports>>,
+    strategy: DynamicImportsMergeStrategy,
 ) -> Result<Vc<DynamicImports>> {
-    let data = graph
-        .await?
-        .enumerate_nodes()
-        .map(|(_, node)| {
-            async move {
-                // TODO: compare module contexts instead?
-                let is_browser = node
-                    .layer
-                    .as_ref()
-                    .is_some_and(|layer| &**layer == "app-client" || &**layer == "client");
-                if !is_browser {
-                    // Only collect in RSC and SSR
-                    if let Some(v) =
-                        &*build_dynamic_imports_map_for_module(client_asset_context, *node.module)
-                            .await?
-                    {
-                        return Ok(Some(v.await?.clone_value()));
+    let dynamic_imports = dynamic_imports
+        .into_iter()
+        .map(|dynamic_imports| async move { dynamic_imports.await })
+        .try_join()
+        .await?;
+    Ok(Vc::cell(merged_dynamic_imports(
+        dynamic_imports
+            .iter()
+            .map(|dynamic_imports| &**dynamic_imports),
+        strategy,
+    )))
+}
+
+fn merged_dynamic_imports<'a, M: Copy + Eq + std::hash::Hash + 'a, I: Clone + PartialEq + 'a>(
+    maps: impl IntoIterator<Item = &'a FxIndexMap<M, Vec<I>>>,
+    strategy: DynamicImportsMergeStrategy,
+) -> FxIndexMap<M, Vec<I>> {
+    let mut merged: FxIndexMap<M, Vec<I>> = FxIndexMap::default();
+    for map in maps {
+        for (module, imports) in map {
+            match (merged.get_mut(module), strategy) {
+                (None, _) => {
+                    merged.insert(*module, imports.clone());
+                }
+                (Some(_), DynamicImportsMergeStrategy::PreferFirst) => {}
+                (Some(merged_imports), DynamicImportsMergeStrategy::Union) => {
+                    for import in imports {
+                        if !merged_imports.contains(import) {
+                            merged_imports.push(import.clone());
+                        }
                     }
                 }
-                Ok(None)
             }
+        }
+    }
+    merged
+}
+
+/// Replaces the client references among the dynamically imported modules with the client modules
+/// behind them. [build_dynamic_imports_map_for_module] already does this, this is for callers
+/// assembling [DynamicImports] from other sources which need the same unified view.
+#[turbo_tasks::function]
+pub async fn resolve_client_references_to_dynamic_imports(
+    dynamic_imports: Vc<DynamicImports>,
+) -> Result<Vc<DynamicImports>> {
+    let resolved = dynamic_imports
+        .await?
+        .iter()
+        .map(|(origin, imports)| async move {
+            let imports = imports
+                .iter()
+                .map(|(import, module)| async move {
+                    Ok((
+                        import.clone(),
+                        client_module_for_dynamic_import(*module).await?,
+                    ))
+                })
+                .try_join()
+                .await?;
+            Ok((*origin, imports))
+        })
+        .try_join()
+        .await?;
+
+    Ok(Vc::cell(resolved.into_iter().collect()))
+}
+
+/// Drops the dynamic imports from or of modules inside `node_modules`, for projects which only want
+/// first-party dynamic imports in the manifests and handle vendor splits separately.
+#[turbo_tasks::function]
+pub async fn first_party_dynamic_imports(
+    dynamic_imports: Vc<DynamicImports>,
+) -> Result<Vc<DynamicImports>> {
+    let first_party = dynamic_imports
+        .await?
+        .iter()
+        .map(|(origin, imports)| async move {
+            if is_in_node_modules(&origin.ident().path().await?.path) {
+                return Ok(None);
+            }
+            let imports = imports
+                .iter()
+                .map(|(import, module)| async move {
+                    Ok((!is_in_node_modules(&module.ident().path().await?.path))
+                        .then(|| (import.clone(), *module)))
+                })
+                .try_flat_join()
+                .await?;
+            Ok((!imports.is_empty()).then_some((*origin, imports)))
         })
         .try_flat_join()
         .await?;
 
-    Ok(Vc::cell(data.into_iter().collect()))
+    Ok(Vc::cell(first_party.into_iter().collect()))
+}
+
+/// Whether the path is inside of a `node_modules` directory.
+fn is_in_node_modules(path: &str) -> bool {
+    path.split('/').any(|segment| segment == "node_modules")
+}
+
+#[turbo_tasks::value(transparent)]
+pub struct DynamicImportRetryOptions(pub Vec<(RcStr, ResolvedVc<DynamicImportRetry>)>);
+
+/// Returns the `retry` options of the `dynamic()` calls in the module, by import source.
+#[turbo_tasks::function]
+pub async fn dynamic_import_retry_options(
+    module: ResolvedVc<Box<dyn Module>>,
+) -> Result<Vc<DynamicImportRetryOptions>> {
+    let Some(ecmascript_asset) =
+        ResolvedVc::try_sidecast::<Box<dyn EcmascriptParsable>>(module).await?
+    else {
+        return Ok(Vc::cell(vec![]));
+    };
+    let ParseResult::Ok { program, .. } = &*ecmascript_asset.failsafe_parse().await? else {
+        return Ok(Vc::cell(vec![]));
+    };
+
+    let is_test_file = is_test_file(&module.ident().path().await?.path);
+    let retry_options = {
+        let mut visitor = DynamicImportVisitor::new(is_test_file);
+        program.visit_with(&mut visitor);
+        visitor.retry_options
+    };
+
+    Ok(Vc::cell(
+        retry_options
+            .into_iter()
+            .map(|(import, retry)| (import, retry.resolved_cell()))
+            .collect(),
+    ))
+}
+
+fn cross_origin_url(base_url: &str, path: &str) -> RcStr {
+    format!("{}/{}", base_url.trim_end_matches('/'), path).into()
+}
+
+/// The id of a dynamic import, as injected into `loadableGenerated` by the next_dynamic transform
+/// and used as the key of `react-loadable-manifest.json`.
+pub(crate) fn dynamic_import_id(origin_path: impl Display, import: &str) -> RcStr {
+    dynamic_import_key(origin_path, import, DYNAMIC_IMPORT_ID_SEPARATOR)
+}
+
+/// The separator between the origin and the import of a [dynamic_import_id].
+const DYNAMIC_IMPORT_ID_SEPARATOR: &str = " -> ";
+
+/// Like [dynamic_import_id], with another separator between the origin and the import, for
+/// runtimes expecting a different key convention.
+pub(crate) fn dynamic_import_key(
+    origin_path: impl Display,
+    import: &str,
+    separator: &str,
+) -> RcStr {
+    format!("{origin_path}{separator}{import}").into()
+}
+
+#[turbo_tasks::function]
+pub async fn resolve_dynamic_imported_chunk_files(
+    dynamic_imported_chunks: Vc<DynamicImportedChunks>,
+    base_path: Vc<FileSystemPath>,
+) -> Result<Vc<DynamicImportedChunkFiles>> {
+    dynamic_imported_chunk_files(dynamic_imported_chunks, base_path, None).await
+}
+
+/// Like [resolve_dynamic_imported_chunk_files], but files outside of the base path, e.g. chunks
+/// hosted on another origin, are kept as absolute URLs: their path prefixed with
+/// `cross_origin_base_url`.
+#[turbo_tasks::function]
+pub async fn resolve_dynamic_imported_chunk_files_cross_origin(
+    dynamic_imported_chunks: Vc<DynamicImportedChunks>,
+    base_path: Vc<FileSystemPath>,
+    cross_origin_base_url: RcStr,
+) -> Result<Vc<DynamicImportedChunkFiles>> {
+    dynamic_imported_chunk_files(
+        dynamic_imported_chunks,
+        base_path,
+        Some(&*cross_origin_base_url),
+    )
+    .await
+}
+
+async fn dynamic_imported_chunk_files(
+    dynamic_imported_chunks: Vc<DynamicImportedChunks>,
+    base_path: Vc<FileSystemPath>,
+    cross_origin_base_url: Option<&str>,
+) -> Result<Vc<DynamicImportedChunkFiles>> {
+    let base_path = &*base_path.await?;
+    let entries = dynamic_imported_chunks
+        .await?
+        .iter()
+        .map(|(origin, dynamic_imports)| async move {
+            let origin_path = &*origin.ident().path().await?;
+            dynamic_imports
+                .iter()
+                .map(|(import, chunk_output)| async move {
+                    let files = chunk_output
+                        .await?
+                        .iter()
+                        .map(|&file| async move {
+                            let file_path = &*file.ident().path().await?;
+                            let path = match base_path.get_path_to(file_path) {
+                                Some(path) => RcStr::from(path),
+                                None => match cross_origin_base_url {
+                                    Some(base_url) => cross_origin_url(base_url, &file_path.path),
+                                    None => return Ok(None),
+                                },
+                            };
+                            Ok(Some((path, file)))
+                        })
+                        .try_flat_join()
+                        .await?;
+                    if let Some(reason) = invalid_dynamic_import_id(&origin_path.path, import) {
+                        InvalidDynamicImportIdIssue {
+                            file_path: origin.ident().path().to_resolved().await?,
+                            module: origin.ident().to_string().await?.clone_value(),
+                            id: dynamic_import_id(origin_path, import),
+                            reason: reason.into(),
+                        }
+                        .resolved_cell()
+                        .emit();
+                        return Ok(None);
+                    }
+                    Ok(Some((dynamic_import_id(origin_path, import), files)))
+                })
+                .try_flat_join()
+                .await
+        })
+        .try_flat_join()
+        .await?;
+
+    // The chunks of the imports folded into the manifest entry of another one are listed under its
+    // id, see `DynamicImportVisitor::folded_import_sources`.
+    let mut files_by_id: FxIndexMap<RcStr, DynamicImportedFiles> = FxIndexMap::default();
+    for (id, files) in entries {
+        let id_files = files_by_id.entry(id).or_default();
+        for file in files {
+            if !id_files.contains(&file) {
+                id_files.push(file);
+            }
+        }
+    }
+
+    Ok(Vc::cell(files_by_id.into_iter().collect()))
+}
+
+/// The output assets referenced by more than one dynamic import, by their path, with the ids of the
+/// dynamic imports sharing them, to find duplication across dynamically loaded chunk groups.
+#[turbo_tasks::value(transparent)]
+pub struct SharedDynamicImportChunks(pub FxIndexMap<RcStr, Vec<RcStr>>);
+
+#[turbo_tasks::function]
+pub async fn shared_dynamic_import_chunks(
+    dynamic_imported_chunks: Vc<DynamicImportedChunks>,
+) -> Result<Vc<SharedDynamicImportChunks>> {
+    let entries = dynamic_imported_chunks
+        .await?
+        .iter()
+        .map(|(origin, dynamic_imports)| async move {
+            let origin_path = &*origin.ident().path().await?;
+            dynamic_imports
+                .iter()
+                .map(|(import, chunk_output)| async move {
+                    let paths = chunk_output
+                        .await?
+                        .iter()
+                        .map(|file| async move { Ok(file.ident().path().await?.path.clone()) })
+                        .try_join()
+                        .await?;
+                    Ok((dynamic_import_id(origin_path, import), paths))
+                })
+                .try_join()
+                .await
+        })
+        .try_flat_join()
+        .await?;
+
+    Ok(Vc::cell(shared_chunks(entries)))
+}
+
+/// Inverts the files of each entry into the entries of each file, keeping the files of more than
+/// one entry only.
+fn shared_chunks<T: Eq + std::hash::Hash>(
+    entries: impl IntoIterator<Item = (RcStr, Vec<T>)>,
+) -> FxIndexMap<T, Vec<RcStr>> {
+    let mut entries_by_file: FxIndexMap<T, Vec<RcStr>> = FxIndexMap::default();
+    for (id, files) in entries {
+        for file in files {
+            let ids = entries_by_file.entry(file).or_default();
+            if !ids.contains(&id) {
+                ids.push(id.clone());
+            }
+        }
+    }
+    entries_by_file.retain(|_, ids| ids.len() > 1);
+    entries_by_file
+}
+
+/// The [DynamicImportedChunkFiles] sorted by id, for `O(log n)` lookups of an id and range queries
+/// (e.g. all dynamic imports of an origin, whose ids share the `${origin} -> ` prefix) in large
+/// projects. [DynamicImportedChunks] stays an [FxIndexMap], as its order is the graph order.
+#[turbo_tasks::value(transparent)]
+pub struct DynamicImportedChunkFilesById(pub BTreeMap<RcStr, DynamicImportedFiles>);
+
+/// Like [resolve_dynamic_imported_chunk_files], but sorted by id. See
+/// [DynamicImportedChunkFilesById].
+#[turbo_tasks::function]
+pub async fn resolve_dynamic_imported_chunk_files_by_id(
+    dynamic_imported_chunks: Vc<DynamicImportedChunks>,
+    base_path: Vc<FileSystemPath>,
+) -> Result<Vc<DynamicImportedChunkFilesById>> {
+    Ok(Vc::cell(
+        resolve_dynamic_imported_chunk_files(dynamic_imported_chunks, base_path)
+            .await?
+            .iter()
+            .cloned()
+            .collect(),
+    ))
+}
+
+/// Dynamic import ids in the order they should be preloaded in, see [dynamic_import_preload_order].
+#[turbo_tasks::value(transparent)]
+pub struct DynamicImportPreloadOrder(pub Vec<RcStr>);
+
+/// Orders the dynamic imports by their dependencies on each other: an import comes before the
+/// dynamic imports of the module it loads, so the runtime can start preloading the outer chunks
+/// first instead of discovering each level only after the previous one loaded. This is metadata
+/// only, the chunks are unchanged.
+///
+/// Modules are matched by path, as the dynamically imported module and the origin of the nested
+/// imports are usually in different layers.
+#[turbo_tasks::function]
+pub async fn dynamic_import_preload_order(
+    dynamic_imports: Vc<DynamicImports>,
+) -> Result<Vc<DynamicImportPreloadOrder>> {
+    let entries = dynamic_imports
+        .await?
+        .iter()
+        .map(|(origin, imports)| async move {
+            let origin_path = &*origin.ident().path().await?;
+            imports
+                .iter()
+                .map(|(import, module)| async move {
+                    Ok(PreloadEntry {
+                        id: dynamic_import_id(origin_path, import),
+                        origin_path: origin_path.path.clone(),
+                        imported_path: module.ident().path().await?.path.clone(),
+                    })
+                })
+                .try_join()
+                .await
+        })
+        .try_flat_join()
+        .await?;
+
+    Ok(Vc::cell(preload_order(&entries)))
+}
+
+struct PreloadEntry {
+    id: RcStr,
+    origin_path: RcStr,
+    imported_path: RcStr,
+}
+
+/// Topologically sorts the entries, keeping their original order where they don't depend on each
+/// other. Entries in a cycle are appended in their original order.
+fn preload_order(entries: &[PreloadEntry]) -> Vec<RcStr> {
+    let mut by_origin: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        by_origin
+            .entry(entry.origin_path.as_str())
+            .or_default()
+            .push(index);
+    }
+
+    let nested = |index: usize| {
+        by_origin
+            .get(&*entries[index].imported_path)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(move |&nested| nested != index)
+    };
+
+    let mut in_degree = vec![0; entries.len()];
+    for index in 0..entries.len() {
+        for nested in nested(index) {
+            in_degree[nested] += 1;
+        }
+    }
+
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..entries.len())
+        .filter(|&index| in_degree[index] == 0)
+        .map(Reverse)
+        .collect();
+    let mut ordered = Vec::with_capacity(entries.len());
+    let mut visited = vec![false; entries.len()];
+    while let Some(Reverse(index)) = ready.pop() {
+        visited[index] = true;
+        ordered.push(entries[index].id.clone());
+        for nested in nested(index) {
+            in_degree[nested] -= 1;
+            if in_degree[nested] == 0 {
+                ready.push(Reverse(nested));
+            }
+        }
+    }
+
+    ordered.extend(
+        entries
+            .iter()
+            .zip(visited)
+            .filter(|(_, visited)| !visited)
+            .map(|(entry, _)| entry.id.clone()),
+    );
+    ordered
+}
+
+/// Dynamic import ids with the number of chunks they load.
+#[turbo_tasks::value(transparent)]
+pub struct DynamicImportChunkCounts(pub Vec<(RcStr, usize)>);
+
+/// Returns the dynamic imports which load more than `chunk_count_threshold` chunks. These are the
+/// candidates for further splitting or for eager loading.
+#[turbo_tasks::function]
+pub async fn dynamic_imports_exceeding_chunk_count(
+    dynamic_imported_chunks: Vc<DynamicImportedChunks>,
+    chunk_count_threshold: usize,
+) -> Result<Vc<DynamicImportChunkCounts>> {
+    let chunk_counts = dynamic_imported_chunks
+        .await?
+        .iter()
+        .map(|(origin, dynamic_imports)| async move {
+            let origin_path = &*origin.ident().path().await?;
+            dynamic_imports
+                .iter()
+                .map(|(import, chunk_output)| async move {
+                    Ok((
+                        dynamic_import_id(origin_path, import),
+                        chunk_output.await?.len(),
+                    ))
+                })
+                .try_join()
+                .await
+        })
+        .try_flat_join()
+        .await?;
+
+    Ok(Vc::cell(filter_chunk_counts(
+        chunk_counts,
+        chunk_count_threshold,
+    )))
+}
+
+fn filter_chunk_counts(
+    chunk_counts: Vec<(RcStr, usize)>,
+    chunk_count_threshold: usize,
+) -> Vec<(RcStr, usize)> {
+    chunk_counts
+        .into_iter()
+        .filter(|(_, chunk_count)| *chunk_count > chunk_count_threshold)
+        .collect()
+}
+
+/// Dynamic import ids which are shared by multiple origin modules, with the idents of those
+/// modules. Only one of them survives in `react-loadable-manifest.json`.
+#[turbo_tasks::value]
+pub struct ModuleIdUniquenessReport {
+    pub duplicates: Vec<(RcStr, Vec<RcStr>)>,
+}
+
+/// Checks that the ids of the dynamic imports are unique. Different origin modules can share the
+/// same path (e.g. the same file in different layers), which leads to duplicate keys in
+/// `react-loadable-manifest.json`. Every collision is reported as an error issue.
+#[turbo_tasks::function]
+pub async fn assert_unique_module_ids(
+    dynamic_imported_chunks: Vc<DynamicImportedChunks>,
+) -> Result<Vc<ModuleIdUniquenessReport>> {
+    let mut origins_by_id: FxIndexMap<RcStr, Vec<ResolvedVc<Box<dyn Module>>>> =
+        FxIndexMap::default();
+    for (origin, dynamic_imports) in dynamic_imported_chunks.await?.iter() {
+        let origin_path = &*origin.ident().path().await?;
+        for (import, _) in dynamic_imports {
+            let origins = origins_by_id
+                .entry(dynamic_import_id(origin_path, import))
+                .or_default();
+            if !origins.contains(origin) {
+                origins.push(*origin);
+            }
+        }
+    }
+
+    let mut duplicates = vec![];
+    for (id, origins) in origins_by_id {
+        if origins.len() < 2 {
+            continue;
+        }
+
+        let origin_idents = origins
+            .iter()
+            .map(|origin| async move { Ok((*origin.ident().to_string().await?).clone()) })
+            .try_join()
+            .await?;
+        DuplicateDynamicImportIdIssue {
+            file_path: origins[0].ident().path().to_resolved().await?,
+            id: id.clone(),
+            origins: origin_idents.clone(),
+            source: module_issue_source(origins[0]).await?,
+        }
+        .resolved_cell()
+        .emit();
+        duplicates.push((id, origin_idents));
+    }
+
+    Ok(ModuleIdUniquenessReport { duplicates }.cell())
+}
+
+/// Why the id of a dynamic import can't be a key of the manifest: the runtime has to look it up by
+/// the id injected by the next_dynamic transform, which is the same string, so an id lossily
+/// decoded from a non-UTF-8 path (with U+FFFD replacement characters) or with control characters
+/// would not match it.
+fn invalid_dynamic_import_id(origin_path: &str, import: &str) -> Option<&'static str> {
+    if origin_path.is_empty() || import.is_empty() {
+        Some("the origin path and the import specifier must not be empty")
+    } else if origin_path.contains('\u{FFFD}') || import.contains('\u{FFFD}') {
+        Some("the path contains bytes which are not valid UTF-8")
+    } else if origin_path.chars().any(char::is_control) || import.chars().any(char::is_control) {
+        Some("the path or the import specifier contains control characters")
+    } else {
+        None
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct InvalidDynamicImportIdIssue {
+    file_path: ResolvedVc<FileSystemPath>,
+    /// The ident of the module containing the dynamic import.
+    module: RcStr,
+    id: RcStr,
+    reason: RcStr,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for InvalidDynamicImportIdIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> Vc<IssueSeverity> {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn stage(&self) -> Vc<IssueStage> {
+        IssueStage::Misc.into()
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> Vc<StyledString> {
+        StyledString::Text("Invalid next/dynamic import id".into()).cell()
+    }
+
+    #[turbo_tasks::function]
+    fn file_path(&self) -> Vc<FileSystemPath> {
+        *self.file_path
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> Vc<OptionStyledString> {
+        Vc::cell(Some(
+            StyledString::Text(
+                format!(
+                    "The dynamic import id {:?} of {} can't be used in the loadable manifest, {}. \
+                     Rename the file or the import.",
+                    self.id, self.module, self.reason
+                )
+                .into(),
+            )
+            .resolved_cell(),
+        ))
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct DuplicateDynamicImportIdIssue {
+    file_path: ResolvedVc<FileSystemPath>,
+    id: RcStr,
+    origins: Vec<RcStr>,
+    source: Option<ResolvedVc<IssueSource>>,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for DuplicateDynamicImportIdIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> Vc<IssueSeverity> {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn stage(&self) -> Vc<IssueStage> {
+        IssueStage::Misc.into()
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> Vc<StyledString> {
+        StyledString::Text("Duplicate next/dynamic import id".into()).cell()
+    }
+
+    #[turbo_tasks::function]
+    fn file_path(&self) -> Vc<FileSystemPath> {
+        *self.file_path
+    }
+
+    #[turbo_tasks::function]
+    fn source(&self) -> Vc<OptionIssueSource> {
+        Vc::cell(self.source)
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> Vc<OptionStyledString> {
+        Vc::cell(Some(
+            StyledString::Stack(
+                std::iter::once(StyledString::Text(
+                    format!(
+                        "The dynamic import id \"{}\" is used by multiple modules, only one of \
+                         them will be preloaded:",
+                        self.id
+                    )
+                    .into(),
+                ))
+                .chain(
+                    self.origins
+                        .iter()
+                        .map(|origin| StyledString::Text(format!("- {origin}").into())),
+                )
+                .collect(),
+            )
+            .resolved_cell(),
+        ))
+    }
+}
+
+/// Dynamic import specifiers which resolve to different modules depending on the origin, with the
+/// idents of those modules.
+#[turbo_tasks::value(transparent)]
+pub struct ConflictingDynamicImportSpecifiers(pub Vec<(RcStr, Vec<RcStr>)>);
+
+/// Checks that every non-relative dynamic import specifier, e.g. `@/components/Chart`, resolves to
+/// the same module across the app. Differing modules are often a sign of misconfigured aliases.
+/// Relative specifiers naturally differ per origin and are not checked. Every conflict is reported
+/// as a warning issue.
+///
+/// Like [validate_dynamic_import_runtimes], this is a verification step which is only run when
+/// explicitly requested.
+#[turbo_tasks::function]
+pub async fn validate_dynamic_import_specifiers(
+    dynamic_imports: Vc<DynamicImports>,
+) -> Result<Vc<ConflictingDynamicImportSpecifiers>> {
+    let dynamic_imports = dynamic_imports.await?;
+    let mut conflicts = vec![];
+    for (specifier, origin, modules) in conflicting_specifiers(
+        dynamic_imports
+            .iter()
+            .map(|(origin, imports)| (*origin, &imports[..])),
+    ) {
+        let module_idents = modules
+            .iter()
+            .map(|module| async move { Ok((*module.ident().to_string().await?).clone()) })
+            .try_join()
+            .await?;
+        ConflictingDynamicImportSpecifierIssue {
+            file_path: origin.ident().path().to_resolved().await?,
+            specifier: specifier.clone(),
+            modules: module_idents.clone(),
+            source: module_issue_source(origin).await?,
+        }
+        .resolved_cell()
+        .emit();
+        conflicts.push((specifier, module_idents));
+    }
+
+    Ok(Vc::cell(conflicts))
+}
+
+/// Groups the non-relative specifiers of the dynamic imports by specifier, returning those which
+/// resolve to more than one module with the first origin importing them and the distinct modules.
+fn conflicting_specifiers<'a, O: Copy, M: Copy + PartialEq + 'a>(
+    entries: impl IntoIterator<Item = (O, &'a [(RcStr, M)])>,
+) -> Vec<(RcStr, O, Vec<M>)> {
+    let mut by_specifier: FxIndexMap<&RcStr, (O, Vec<M>)> = FxIndexMap::default();
+    for (origin, imports) in entries {
+        for (specifier, module) in imports {
+            if specifier.starts_with('.') || specifier.starts_with('/') {
+                continue;
+            }
+            let (_, modules) = by_specifier
+                .entry(specifier)
+                .or_insert_with(|| (origin, vec![]));
+            if !modules.contains(module) {
+                modules.push(*module);
+            }
+        }
+    }
+
+    by_specifier
+        .into_iter()
+        .filter(|(_, (_, modules))| modules.len() > 1)
+        .map(|(specifier, (origin, modules))| (specifier.clone(), origin, modules))
+        .collect()
+}
+
+#[turbo_tasks::value(shared)]
+struct ConflictingDynamicImportSpecifierIssue {
+    file_path: ResolvedVc<FileSystemPath>,
+    specifier: RcStr,
+    modules: Vec<RcStr>,
+    source: Option<ResolvedVc<IssueSource>>,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for ConflictingDynamicImportSpecifierIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> Vc<IssueSeverity> {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn stage(&self) -> Vc<IssueStage> {
+        IssueStage::Resolve.into()
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> Vc<StyledString> {
+        StyledString::Text("next/dynamic import specifier resolves to different modules".into())
+            .cell()
+    }
+
+    #[turbo_tasks::function]
+    fn file_path(&self) -> Vc<FileSystemPath> {
+        *self.file_path
+    }
+
+    #[turbo_tasks::function]
+    fn source(&self) -> Vc<OptionIssueSource> {
+        Vc::cell(self.source)
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> Vc<OptionStyledString> {
+        Vc::cell(Some(
+            StyledString::Stack(
+                std::iter::once(StyledString::Text(
+                    format!(
+                        "The dynamic import \"{}\" resolves to different modules depending on the \
+                         importing module, check the configured aliases:",
+                        self.specifier
+                    )
+                    .into(),
+                ))
+                .chain(
+                    self.modules
+                        .iter()
+                        .map(|module| StyledString::Text(format!("- {module}").into())),
+                )
+                .collect(),
+            )
+            .resolved_cell(),
+        ))
+    }
+}
+
+/// Dynamic import ids whose module imports Node.js-only builtins, with those builtins.
+#[turbo_tasks::value(transparent)]
+pub struct DynamicImportRuntimeViolations(pub Vec<(RcStr, Vec<RcStr>)>);
+
+/// Checks that no dynamically imported module imports a Node.js-only builtin such as `fs`, when
+/// its origin is rendered in the browser as well (see [renders_in_browser]). The dynamic imports of
+/// server-only layers, e.g. those of React Server Components, are never loaded in the browser and
+/// not checked. Every violation is reported as an error issue.
+///
+/// This is a verification step which has to parse every dynamically imported module again, so it
+/// is not part of the regular build and only run when explicitly requested.
+#[turbo_tasks::function]
+pub async fn validate_dynamic_import_runtimes(
+    dynamic_imports: Vc<DynamicImports>,
+) -> Result<Vc<DynamicImportRuntimeViolations>> {
+    let mut violations = vec![];
+    for (origin, imports) in dynamic_imports.await?.iter() {
+        let origin_ident = origin.ident().await?;
+        if let Some(layer) = origin_ident.layer {
+            if !renders_in_browser(&layer.await?) {
+                continue;
+            }
+        }
+        let origin_path = &*origin_ident.path.await?;
+        for (import, module) in imports {
+            let Some(ecmascript_asset) =
+                ResolvedVc::try_sidecast::<Box<dyn EcmascriptParsable>>(*module).await?
+            else {
+                continue;
+            };
+            let ParseResult::Ok { program, .. } = &*ecmascript_asset.failsafe_parse().await? else {
+                continue;
+            };
+
+            let node_builtins = node_builtin_imports(program);
+            if node_builtins.is_empty() {
+                continue;
+            }
+
+            let id = dynamic_import_id(origin_path, import);
+            DynamicImportRuntimeIssue {
+                file_path: origin.ident().path().to_resolved().await?,
+                id: id.clone(),
+                node_builtins: node_builtins.clone(),
+            }
+            .resolved_cell()
+            .emit();
+            violations.push((id, node_builtins));
+        }
+    }
+
+    Ok(Vc::cell(violations))
+}
+
+/// Whether the modules of `layer` are rendered in the browser after being rendered on the server,
+/// so their next/dynamic imports are loaded there as well.
+fn renders_in_browser(layer: &str) -> bool {
+    matches!(layer, "ssr" | "edge-ssr" | "app-ssr" | "app-edge-ssr")
+}
+
+/// Node.js builtins without a browser fallback, including everything using the `node:` scheme.
+fn is_node_only_builtin(specifier: &str) -> bool {
+    const NODE_ONLY_BUILTINS: &[&str] = &[
+        "async_hooks",
+        "child_process",
+        "cluster",
+        "dgram",
+        "dns",
+        "fs",
+        "fs/promises",
+        "http2",
+        "inspector",
+        "module",
+        "net",
+        "perf_hooks",
+        "readline",
+        "repl",
+        "tls",
+        "v8",
+        "worker_threads",
+    ];
+    specifier.starts_with("node:") || NODE_ONLY_BUILTINS.contains(&specifier)
+}
+
+/// The Node.js-only builtins imported by a module via `import`, `import()` or `require()`.
+fn node_builtin_imports(program: &Program) -> Vec<RcStr> {
+    let mut visitor = NodeBuiltinImportVisitor::default();
+    program.visit_with(&mut visitor);
+    visitor.node_builtins
+}
+
+#[derive(Default)]
+struct NodeBuiltinImportVisitor {
+    node_builtins: Vec<RcStr>,
+}
+
+impl NodeBuiltinImportVisitor {
+    fn add(&mut self, specifier: &str) {
+        if is_node_only_builtin(specifier) && !self.node_builtins.iter().any(|b| b == specifier) {
+            self.node_builtins.push(specifier.into());
+        }
+    }
+}
+
+impl Visit for NodeBuiltinImportVisitor {
+    fn visit_import_decl(&mut self, decl: &ImportDecl) {
+        self.add(&decl.src.value);
+    }
+
+    fn visit_call_expr(&mut self, call_expr: &CallExpr) {
+        let is_import_or_require = match &call_expr.callee {
+            Callee::Import(_) => true,
+            Callee::Expr(callee) => {
+                matches!(&**callee, Expr::Ident(ident) if &*ident.sym == "require")
+            }
+            _ => false,
+        };
+        if is_import_or_require {
+            if let Some(Expr::Lit(Lit::Str(str_))) = call_expr.args.first().map(|arg| &*arg.expr) {
+                self.add(&str_.value);
+            }
+        }
+
+        call_expr.visit_children_with(self);
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct DynamicImportRuntimeIssue {
+    file_path: ResolvedVc<FileSystemPath>,
+    id: RcStr,
+    node_builtins: Vec<RcStr>,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for DynamicImportRuntimeIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> Vc<IssueSeverity> {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn stage(&self) -> Vc<IssueStage> {
+        IssueStage::Analysis.into()
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> Vc<StyledString> {
+        StyledString::Text("next/dynamic import of a Node.js-only module".into()).cell()
+    }
+
+    #[turbo_tasks::function]
+    fn file_path(&self) -> Vc<FileSystemPath> {
+        *self.file_path
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> Vc<OptionStyledString> {
+        Vc::cell(Some(
+            StyledString::Text(
+                format!(
+                    "The dynamic import \"{}\" is loaded in the browser, but imports {}, which is \
+                     only available in Node.js.",
+                    self.id,
+                    self.node_builtins.join(", ")
+                )
+                .into(),
+            )
+            .resolved_cell(),
+        ))
+    }
+}
+
+#[turbo_tasks::function]
+pub async fn map_next_dynamic(
+    graph: Vc<SingleModuleGraph>,
+    client_asset_context: Vc<Box<dyn AssetContext>>,
+) -> Result<Vc<DynamicImports>> {
+    let data = graph
+        .await?
+        .enumerate_nodes()
+        .map(|(_, node)| dynamic_imports_for_node(node, client_asset_context))
+        .try_flat_join()
+        .await?;
+
+    Ok(Vc::cell(data.into_iter().collect()))
+}
+
+/// Updates the result of a previous [map_next_dynamic] call on `graph` after `changed_modules`
+/// changed. Only the changed modules and the modules directly importing them are analyzed again,
+/// the entries of all other modules are taken from `previous`. The entries are in graph order, the
+/// same as the ones of a full [map_next_dynamic] run.
+#[turbo_tasks::function]
+pub async fn map_next_dynamic_incremental(
+    graph: Vc<SingleModuleGraph>,
+    client_asset_context: Vc<Box<dyn AssetContext>>,
+    previous: Vc<DynamicImports>,
+    changed_modules: Vec<ResolvedVc<Box<dyn Module>>>,
+) -> Result<Vc<DynamicImports>> {
+    let graph = graph.await?;
+    let affected_modules = &graph.with_direct_importers(&changed_modules);
+    let previous = &*previous.await?;
+
+    let data = graph
+        .enumerate_nodes()
+        .map(|(_, node)| async move {
+            if affected_modules.contains(&node.module) {
+                dynamic_imports_for_node(node, client_asset_context).await
+            } else {
+                Ok(previous
+                    .get(&node.module)
+                    .map(|imports| (node.module, imports.clone())))
+            }
+        })
+        .try_flat_join()
+        .await?;
+
+    Ok(Vc::cell(data.into_iter().collect()))
+}
+
+/// A batch of the results of [map_next_dynamic], see [map_next_dynamic_batch].
+#[turbo_tasks::value]
+pub struct DynamicImportsBatch {
+    pub dynamic_imports: ResolvedVc<DynamicImports>,
+    /// The cursor of the next batch, `None` if this is the last one.
+    pub next_cursor: Option<usize>,
+}
+
+/// Like [map_next_dynamic], but only analyzes `batch_size` graph nodes starting at `cursor`, so
+/// whole-graph tooling can consume the results with bounded memory. Start with a `cursor` of `0`
+/// and continue with `next_cursor` until it is `None`.
+///
+/// Every batch is a separate task, so a batch is memoized and invalidated independently. This
+/// bounds what a consumer has to hold at once, but not what turbo-tasks caches: the analysis of
+/// every module stays cached as usual. A visitor callback is not offered since task arguments
+/// can't be closures without giving up on caching.
+#[turbo_tasks::function]
+pub async fn map_next_dynamic_batch(
+    graph: Vc<SingleModuleGraph>,
+    client_asset_context: Vc<Box<dyn AssetContext>>,
+    cursor: usize,
+    batch_size: usize,
+) -> Result<Vc<DynamicImportsBatch>> {
+    if batch_size == 0 {
+        bail!("batch_size must be greater than 0");
+    }
+
+    let graph = graph.await?;
+    let data = graph
+        .enumerate_nodes()
+        .skip(cursor)
+        .take(batch_size)
+        .map(|(_, node)| dynamic_imports_for_node(node, client_asset_context))
+        .try_flat_join()
+        .await?;
+
+    let next_cursor = cursor.saturating_add(batch_size);
+    Ok(DynamicImportsBatch {
+        dynamic_imports: ResolvedVc::cell(data.into_iter().collect()),
+        next_cursor: (next_cursor < graph.iter_nodes().count()).then_some(next_cursor),
+    }
+    .cell())
+}
+
+/// The router a module belongs to, from its layer: the layers of the app router are prefixed with
+/// `app-` (e.g. `app-rsc`, `app-ssr`), those of the pages router aren't (e.g. `ssr`, `edge-ssr`).
+pub(crate) fn dynamic_import_router(layer: &str) -> LoadableRouter {
+    if layer.starts_with("app-") {
+        LoadableRouter::App
+    } else {
+        LoadableRouter::Pages
+    }
+}
+
+async fn dynamic_imports_for_node(
+    node: &SingleModuleGraphNode,
+    client_asset_context: Vc<Box<dyn AssetContext>>,
+) -> Result<Option<(ResolvedVc<Box<dyn Module>>, DynamicImportedModules)>> {
+    // TODO: compare module contexts instead?
+    let is_browser = node
+        .layer
+        .as_ref()
+        .is_some_and(|layer| &**layer == "app-client" || &**layer == "client");
+    if !is_browser {
+        // Only collect in RSC and SSR
+        if let Some(v) =
+            &*build_dynamic_imports_map_for_module(client_asset_context, *node.module).await?
+        {
+            return Ok(Some(v.await?.clone_value()));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use next_core::mode::NextMode;
+    use swc_core::{
+        common::{comments::SingleThreadedComments, sync::Lrc, FileName, SourceMap},
+        ecma::{
+            ast::EsVersion,
+            parser::{parse_file_as_program, Syntax, TsSyntax},
+        },
+    };
+    use turbo_tasks::{CollectiblesSource, TurboTasks};
+    use turbo_tasks_fs::{File, FileSystem, VirtualFileSystem};
+    use turbo_tasks_memory::MemoryBackend;
+    use turbopack::ModuleAssetContext;
+    use turbopack_browser::BrowserChunkingContext;
+    use turbopack_core::{
+        asset::AssetContent,
+        compile_time_info::CompileTimeInfo,
+        environment::{BrowserEnvironment, Environment, ExecutionEnvironment},
+        issue::OptionIssueProcessingPathItems,
+        reference_type::ReferenceType,
+        source_pos::SourcePos,
+        virtual_source::VirtualSource,
+    };
+
+    use super::*;
+
+    fn browser_environment() -> Vc<Environment> {
+        Environment::new(Value::new(ExecutionEnvironment::Browser(
+            BrowserEnvironment {
+                dom: true,
+                web_worker: false,
+                service_worker: false,
+                browserslist_query: "last 1 Chrome versions".into(),
+            }
+            .resolved_cell(),
+        )))
+    }
+
+    fn test_asset_context(layer: &str) -> Vc<Box<dyn AssetContext>> {
+        Vc::upcast(ModuleAssetContext::new(
+            Default::default(),
+            CompileTimeInfo::new(browser_environment()),
+            Default::default(),
+            Default::default(),
+            Vc::cell(layer.into()),
+        ))
+    }
+
+    /// A module on `layer` at `path` of `root`.
+    async fn test_module(
+        root: Vc<FileSystemPath>,
+        layer: &str,
+        path: &str,
+        code: &str,
+    ) -> Result<ResolvedVc<Box<dyn Module>>> {
+        let source = VirtualSource::new(
+            root.join(path.into()),
+            AssetContent::file(File::from(code).into()),
+        );
+        test_asset_context(layer)
+            .process(Vc::upcast(source), Value::new(ReferenceType::Undefined))
+            .module()
+            .to_resolved()
+            .await
+    }
+
+    async fn browser_chunking_context(
+        root: Vc<FileSystemPath>,
+    ) -> Result<Vc<Box<dyn ChunkingContext>>> {
+        let output_root = root.join("out".into()).to_resolved().await?;
+        Ok(Vc::upcast(
+            BrowserChunkingContext::builder(
+                root.to_resolved().await?,
+                output_root,
+                output_root,
+                root.join("out/chunks".into()).to_resolved().await?,
+                root.join("out/media".into()).to_resolved().await?,
+                browser_environment().to_resolved().await?,
+                NextMode::Development.runtime_type(),
+            )
+            .build(),
+        ))
+    }
+
+    fn parse(code: &str) -> (Program, SingleThreadedComments) {
+        let cm = Lrc::new(SourceMap::default());
+        let fm = cm.new_source_file(FileName::Anon.into(), code.to_string());
+        let comments = SingleThreadedComments::default();
+        let program = parse_file_as_program(
+            &fm,
+            Syntax::Typescript(TsSyntax {
+                tsx: true,
+                ..Default::default()
+            }),
+            EsVersion::latest(),
+            Some(&comments),
+            &mut vec![],
+        )
+        .unwrap();
+        (program, comments)
+    }
+
+    fn visit_program(code: &str) -> DynamicImportVisitor<'static> {
+        visit_program_with(code, DynamicImportVisitor::new(false))
+    }
+
+    fn visit_program_with<'a>(
+        code: &str,
+        mut visitor: DynamicImportVisitor<'a>,
+    ) -> DynamicImportVisitor<'a> {
+        let (program, _) = parse(code);
+        program.visit_with(&mut visitor);
+        visitor
+    }
+
+    fn collect_import_sources(code: &str) -> Vec<RcStr> {
+        visit_program(code).import_sources
+    }
+
+    #[test]
+    fn orders_nested_dynamic_imports_after_their_parents() {
+        let entry = |origin: &str, import: &str, imported: &str| PreloadEntry {
+            id: dynamic_import_id(origin, import),
+            origin_path: origin.into(),
+            imported_path: imported.into(),
+        };
+
+        assert_eq!(
+            preload_order(&[
+                entry("components/b.js", "./c", "components/c.js"),
+                entry("pages/index.js", "../components/a", "components/a.js"),
+                entry("pages/other.js", "../components/d", "components/d.js"),
+                entry("components/a.js", "./b", "components/b.js"),
+            ]),
+            vec![
+                "pages/index.js -> ../components/a",
+                "pages/other.js -> ../components/d",
+                "components/a.js -> ./b",
+                "components/b.js -> ./c",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_dynamic_import_cycles_in_original_order() {
+        let entry = |origin: &str, imported: &str| PreloadEntry {
+            id: dynamic_import_id(origin, imported),
+            origin_path: origin.into(),
+            imported_path: imported.into(),
+        };
+
+        assert_eq!(
+            preload_order(&[entry("a.js", "b.js"), entry("b.js", "a.js")]),
+            vec!["a.js -> b.js", "b.js -> a.js"]
+        );
+    }
+
+    #[test]
+    fn finds_node_builtins_of_dynamically_imported_module() {
+        // `path` has a browser fallback, the other builtins don't.
+        let (program, _) = parse(
+            r#"
+            import { readFileSync } from 'fs'
+            import path from 'path'
+            const cp = require('node:child_process')
+
+            export default function UsesFs() {
+                return readFileSync(path.join(__dirname, 'data.txt'), 'utf8')
+            }
+            "#,
+        );
+
+        assert_eq!(
+            node_builtin_imports(&program),
+            vec!["fs", "node:child_process"]
+        );
+    }
+
+    #[tokio::test]
+    async fn points_unresolved_dynamic_imports_at_their_specifier() {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = Vc::upcast::<Box<dyn FileSystem>>(VirtualFileSystem::new()).root();
+            let origin = test_module(
+                root,
+                "ssr",
+                "pages/index.js",
+                "import dynamic from 'next/dynamic'\nconst Missing = dynamic(() => \
+                 import('./missing'))",
+            )
+            .await?;
+
+            let dynamic_imports =
+                build_dynamic_imports_map_for_module(test_asset_context("client"), origin);
+            dynamic_imports.strongly_consistent().await?;
+
+            let mut ranges = vec![];
+            for issue in dynamic_imports.peek_collectibles::<Box<dyn Issue>>() {
+                let issue = issue
+                    .into_plain(OptionIssueProcessingPathItems::none())
+                    .await?;
+                if issue.stage == IssueStage::Resolve {
+                    ranges.push(issue.source.as_ref().and_then(|source| source.range));
+                }
+            }
+            // The specifier `'./missing'` on the second line.
+            assert_eq!(
+                ranges,
+                vec![Some((
+                    SourcePos {
+                        line: 1,
+                        column: 37
+                    },
+                    SourcePos {
+                        line: 1,
+                        column: 48
+                    }
+                ))]
+            );
+
+            anyhow::Ok(())
+        })
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn renders_ssr_layers_in_browser() {
+        for layer in ["ssr", "edge-ssr", "app-ssr", "app-edge-ssr"] {
+            assert!(renders_in_browser(layer), "{layer}");
+        }
+        for layer in ["app-rsc", "app-edge-rsc", "app-route", "api", "middleware"] {
+            assert!(!renders_in_browser(layer), "{layer}");
+        }
+    }
+
+    #[tokio::test]
+    async fn only_validates_runtimes_of_origins_rendered_in_browser() {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = Vc::upcast::<Box<dyn FileSystem>>(VirtualFileSystem::new()).root();
+            let ssr_origin = test_module(root, "ssr", "pages/index.js", "").await?;
+            let rsc_origin = test_module(root, "app-rsc", "app/page.js", "").await?;
+            let uses_fs = test_module(
+                root,
+                "client",
+                "components/uses-fs.js",
+                "import { readFileSync } from 'fs'\nexport default readFileSync",
+            )
+            .await?;
+            let dynamic_imports = Vc::<DynamicImports>::cell(FxIndexMap::from_iter([
+                (ssr_origin, vec![("../components/uses-fs".into(), uses_fs)]),
+                (rsc_origin, vec![("../components/uses-fs".into(), uses_fs)]),
+            ]));
+
+            let violations = validate_dynamic_import_runtimes(dynamic_imports).await?;
+            let [(id, node_builtins)] = &violations[..] else {
+                panic!("expected one violation, got {:?}", *violations);
+            };
+            assert!(
+                id.ends_with("pages/index.js -> ../components/uses-fs"),
+                "{id}"
+            );
+            assert_eq!(node_builtins, &vec![RcStr::from("fs")]);
+
+            anyhow::Ok(())
+        })
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn filters_chunk_counts_above_threshold() {
+        let chunk_counts = vec![
+            ("pages/index.js -> ./light".into(), 1),
+            ("pages/index.js -> ./exact".into(), 3),
+            ("pages/index.js -> ./heavy".into(), 7),
+        ];
+
+        assert_eq!(
+            filter_chunk_counts(chunk_counts, 3),
+            vec![(RcStr::from("pages/index.js -> ./heavy"), 7)]
+        );
+    }
+
+    #[test]
+    fn collects_declared_modules_option() {
+        assert_eq!(
+            collect_import_sources(
+                r#"
+                import dynamic from 'next/dynamic'
+
+                const Hello = dynamic(() => import('./hello'), {
+                    modules: ['./hello', './hello-styles'],
+                })
+                const Legacy = dynamic({
+                    loader: () => import('./legacy'),
+                    modules: ['./legacy-dependency'],
+                })
+                "#
+            ),
+            vec![
+                "./hello",
+                "./hello-styles",
+                "./legacy",
+                "./legacy-dependency"
+            ]
+        );
+    }
+
+    #[test]
+    fn collects_dynamic_wrapped_in_react_memo() {
+        assert_eq!(
+            collect_import_sources(
+                r#"
+                import React from 'react'
+                import dynamic from 'next/dynamic'
+
+                const Comp = React.memo(dynamic(() => import('./X')))
+                "#
+            ),
+            vec!["./X"]
+        );
+    }
+
+    #[test]
+    fn collects_dynamic_wrapped_in_forward_ref() {
+        assert_eq!(
+            collect_import_sources(
+                r#"
+                import { forwardRef, memo } from 'react'
+                import dynamic from 'next/dynamic'
+
+                const Input = forwardRef<HTMLInputElement, Props>((props, ref) => {
+                    const Field = dynamic<FieldProps>(() => import('./Field'))
+                    return <Field {...props} inputRef={ref} />
+                })
+                const Chart = memo<ChartProps>(dynamic<ChartProps>(() => import('./Chart')))
+                "#
+            ),
+            vec!["./Field", "./Chart"]
+        );
+    }
+
+    #[test]
+    fn collects_dynamic_behind_typescript_wrappers() {
+        assert_eq!(
+            collect_import_sources(
+                r#"
+                import dynamic from 'next/dynamic'
+
+                const A = dynamic!(() => import('./A'))
+                const B = (dynamic as typeof dynamic)(() => import('./B'))
+                const C = (dynamic<Props>)(() => import('./C'))
+                "#
+            ),
+            vec!["./A", "./B", "./C"]
+        );
+    }
+
+    #[test]
+    fn collects_react_lazy_separately() {
+        let visitor = visit_program(
+            r#"
+            import React, { lazy as reactLazy } from 'react'
+            import dynamic from 'next/dynamic'
+
+            const Dynamic = dynamic(() => import('./hello'))
+            const Lazy = React.lazy(() => import('./hello.js'))
+            const Named = reactLazy(() => import('./other'))
+            "#,
+        );
+        assert_eq!(visitor.import_sources, vec!["./hello"]);
+        assert_eq!(visitor.lazy_import_sources, vec!["./hello.js", "./other"]);
+    }
+
+    #[test]
+    fn collects_renamed_next_dynamic_with_side_imports() {
+        for imports in [
+            "import dynamicImport from 'next/dynamic'",
+            "import dynamicImport, { type DynamicOptions } from 'next/dynamic'",
+            "import { type DynamicOptions, default as dynamicImport } from 'next/dynamic'",
+            "import { useState } from 'react'\nimport dynamicImport from 'next/dynamic'",
+            "import dynamicImport from 'next/dynamic'\nimport type dynamic from 'next/dynamic'",
+            "import type { DynamicOptions } from 'next/dynamic'\nimport dynamicImport from \
+             'next/dynamic'",
+            "import dynamicImport from 'next/dynamic'\nimport { dynamic } from './dynamic'",
+        ] {
+            let visitor = visit_program(&format!(
+                "{imports}\nconst Hello = dynamicImport(() => import('./hello'))"
+            ));
+            assert_eq!(visitor.import_sources, vec!["./hello"], "{imports}");
+        }
+    }
+
+    #[test]
+    fn checks_loadable_generated_is_present() {
+        let transformed = visit_program(
+            r#"
+            import dynamic from 'next/dynamic'
+
+            const Hello = dynamic(() => import('./hello'), {
+                loadableGenerated: { modules: ["pages/index.js -> ./hello"] },
+            })
+            "#,
+        );
+        assert!(!transformed.missing_loadable_generated);
+
+        let untransformed = visit_program(
+            r#"
+            import dynamic from 'next/dynamic'
+
+            const Hello = dynamic(() => import('./hello'), {
+                loadableGenerated: { modules: ["pages/index.js -> ./hello"] },
+            })
+            const Other = dynamic(() => import('./other'), { ssr: false })
+            "#,
+        );
+        assert!(untransformed.missing_loadable_generated);
+    }
+
+    #[test]
+    fn ignores_missing_loadable_generated_of_calls_the_transform_skips() {
+        for code in [
+            // The transform binds the default specifier only.
+            "import { default as dynamic } from 'next/dynamic'\nconst Hello = dynamic(() => \
+             import('./hello'))",
+            // It matches a plain identifier callee only.
+            "import dynamic from 'next/dynamic'\nconst Hello = (dynamic as any)(() => \
+             import('./hello'))",
+            // It can't capture an import with expressions.
+            "import dynamic from 'next/dynamic'\nconst Hello = dynamic(() => import(`./${name}`))",
+        ] {
+            assert!(!visit_program(code).missing_loadable_generated, "{code}");
+        }
+    }
+
+    #[test]
+    fn collects_dynamic_in_jsx_render_props_and_fragments() {
+        assert_eq!(
+            collect_import_sources(
+                r#"
+                import dynamic from 'next/dynamic'
+
+                export default function Page() {
+                    return (
+                        <>
+                            <Loader>{() => dynamic(() => import('./render-prop'))}</Loader>
+                            <>{show && dynamic(() => import('./fragment'))}</>
+                            <Loader render={() => dynamic(() => import('./attribute'))} />
+                        </>
+                    )
+                }
+                "#
+            ),
+            vec!["./render-prop", "./fragment", "./attribute"]
+        );
+    }
+
+    #[test]
+    fn collects_specifiers_captured_by_the_transform() {
+        // The transform only generates manifest ids for string literals and templates without
+        // expressions, anything else would get an entry which is never looked up.
+        assert_eq!(
+            collect_import_sources(
+                r#"
+                import dynamic from 'next/dynamic'
+
+                const Concat = dynamic(() => import('./components/' + 'concat'))
+                const Raw = dynamic(() => import(String.raw`./components/raw`))
+                const Template = dynamic(() => import(`./components/template`))
+                const Literal = dynamic(() => import('./components/literal'))
+                "#
+            ),
+            vec!["./components/template", "./components/literal"]
+        );
+    }
+
+    #[test]
+    fn skips_modules_with_directive() {
+        let has_directive = |code: &str| {
+            let (program, comments) = parse(code);
+            skips_dynamic_collection(&comments, &program)
+        };
+
+        assert!(has_directive(
+            r#"
+            // @next-skip-dynamic-collection
+            import dynamic from 'next/dynamic'
+
+            const Vendored = dynamic(() => import('./vendored'))
+            "#
+        ));
+        assert!(has_directive(
+            "/* @next-skip-dynamic-collection */\nimport dynamic from 'next/dynamic'"
+        ));
+        assert!(!has_directive(
+            r#"
+            import dynamic from 'next/dynamic'
+            // @next-skip-dynamic-collection
+            const Hello = dynamic(() => import('./hello'))
+            "#
+        ));
+    }
+
+    #[test]
+    fn detects_jsx_pragmas() {
+        let (program, comments) = parse(
+            r#"
+            /**
+             * @jsxRuntime classic
+             * @jsxImportSource preact
+             */
+            import dynamic from 'next/dynamic'
+
+            const Hello = dynamic(() => import('./hello'))
+            const Lazy = React.lazy(() => import('./lazy'))
+            "#,
+        );
+        let mut visitor = DynamicImportVisitor::new(false).with_comments(&comments);
+        program.visit_with(&mut visitor);
+
+        assert_eq!(
+            visitor.jsx_pragmas,
+            JsxPragmas {
+                runtime: Some("classic".into()),
+                import_source: Some("preact".into()),
+            }
+        );
+        assert_eq!(visitor.import_sources, vec!["./hello"]);
+        assert_eq!(visitor.lazy_import_sources, vec!["./lazy"]);
+    }
+
+    #[test]
+    fn collects_numeric_chunk_names() {
+        let (program, comments) = parse(
+            r#"
+            import dynamic from 'next/dynamic'
+
+            const Legacy = dynamic(() => import(/* webpackChunkName: "123" */ './legacy'))
+            const Named = dynamic(() => import(/* webpackChunkName: 'named' */ './named'))
+            const Plain = dynamic(() => import('./plain'))
+            "#,
+        );
+        let mut visitor = DynamicImportVisitor::new(false).with_comments(&comments);
+        program.visit_with(&mut visitor);
+
+        let chunk_names = visitor
+            .numeric_chunk_names
+            .iter()
+            .map(|(chunk_name, _)| chunk_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(chunk_names, vec!["123"]);
+    }
+
+    #[test]
+    fn parses_webpack_chunk_names() {
+        assert_eq!(
+            webpack_chunk_name(r#" webpackChunkName: "123" "#).as_deref(),
+            Some("123")
+        );
+        assert_eq!(
+            webpack_chunk_name(" webpackPrefetch: true, webpackChunkName:'hello' ").as_deref(),
+            Some("hello")
+        );
+        assert_eq!(webpack_chunk_name(" webpackChunkName: hello "), None);
+        assert_eq!(webpack_chunk_name(" @jsxRuntime classic "), None);
+    }
+
+    #[test]
+    fn pre_scans_sources() {
+        assert!(may_contain_dynamic_imports(
+            "import dynamic from 'next/dynamic'"
+        ));
+        assert!(may_contain_dynamic_imports(
+            "const Lazy = React.lazy(() => import('./lazy'))"
+        ));
+        assert!(!may_contain_dynamic_imports("export * from './x'"));
+        assert!(!may_contain_dynamic_imports(
+            "export const add = (a, b) => a + b"
+        ));
+    }
+
+    #[test]
+    fn merges_lazy_imports_into_next_dynamic_ones() {
+        let imports = vec![(RcStr::from("./chart"), 1), (RcStr::from("../chart"), 1)];
+        let lazy_imports = vec![(RcStr::from("./chart.js"), 1), (RcStr::from("./table"), 2)];
+        assert_eq!(
+            merge_lazy_imports(imports, lazy_imports),
+            vec![
+                (RcStr::from("./chart"), 1),
+                (RcStr::from("../chart"), 1),
+                (RcStr::from("./table"), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_dynamic_imports_of_graphs() {
+        let server = FxIndexMap::from_iter([
+            ("pages/index.js", vec![("./chart", "chart.js")]),
+            ("pages/about.js", vec![("./team", "team.js")]),
+        ]);
+        let edge = FxIndexMap::from_iter([
+            (
+                "pages/index.js",
+                vec![("./table", "table.js"), ("./chart", "chart.js")],
+            ),
+            ("pages/blog.js", vec![("./post", "post.js")]),
+        ]);
+
+        assert_eq!(
+            merged_dynamic_imports([&server, &edge], DynamicImportsMergeStrategy::PreferFirst),
+            FxIndexMap::from_iter([
+                ("pages/index.js", vec![("./chart", "chart.js")]),
+                ("pages/about.js", vec![("./team", "team.js")]),
+                ("pages/blog.js", vec![("./post", "post.js")]),
+            ])
+        );
+        assert_eq!(
+            merged_dynamic_imports([&server, &edge], DynamicImportsMergeStrategy::Union),
+            FxIndexMap::from_iter([
+                (
+                    "pages/index.js",
+                    vec![("./chart", "chart.js"), ("./table", "table.js")]
+                ),
+                ("pages/about.js", vec![("./team", "team.js")]),
+                ("pages/blog.js", vec![("./post", "post.js")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn collects_top_level_await_imports() {
+        let code = r#"
+            import dynamic from 'next/dynamic'
+
+            const config = await import('./config')
+            const { default: Chart } = await (import('./chart'))
+            const Table = dynamic(() => import('./table'))
+
+            async function load() {
+                return await import('./nested')
+            }
+            class Loader {
+                static async load() {
+                    await import('./method')
+                }
+            }
+            "#;
+        let (program, _) = parse(code);
+        let mut visitor = TopLevelAwaitImportVisitor {
+            import_sources: vec![],
+        };
+        program.visit_with(&mut visitor);
+        assert_eq!(visitor.import_sources, vec!["./config", "./chart"]);
+
+        // They don't end up with the next/dynamic imports.
+        assert_eq!(visit_program(code).import_sources, vec!["./table"]);
+    }
+
+    #[test]
+    fn ignores_optional_calls() {
+        // The next/dynamic transform doesn't rewrite them, so they wouldn't get an id to preload.
+        let visitor = visit_program(
+            r#"
+            import dynamic from 'next/dynamic'
+
+            const Chart = dynamic?.(() => import('./chart'), { ssr: false })
+            "#,
+        );
+        assert!(visitor.import_sources.is_empty());
+    }
+
+    #[test]
+    fn collects_enum_keyed_imports() {
+        let visitor = visit_program(
+            r#"
+            import dynamic from 'next/dynamic'
+
+            enum Kind { Bar, Line }
+
+            const Chart = dynamic(() => ({
+                [Kind.Bar]: () => import('./bar-chart'),
+                [Kind.Line]: (() => import('./line-chart')),
+            })[kind]())
+            "#,
+        );
+        assert_eq!(visitor.import_sources, vec!["./line-chart"]);
+        assert_eq!(
+            visitor.folded_import_sources,
+            vec![(RcStr::from("./line-chart"), RcStr::from("./bar-chart"))]
+        );
+    }
+
+    #[test]
+    fn collects_imports_iterated_over() {
+        let visitor = visit_program(
+            r#"
+            import dynamic from 'next/dynamic'
+
+            const Widgets = dynamic(async () => {
+                const widgets = []
+                for await (const mod of [import('./clock'), import('./weather')]) {
+                    widgets.push(mod.default)
+                }
+                return () => widgets.map((Widget) => <Widget />)
+            })
+            "#,
+        );
+        assert_eq!(visitor.import_sources, vec!["./weather"]);
+        assert_eq!(
+            visitor.folded_import_sources,
+            vec![(RcStr::from("./weather"), RcStr::from("./clock"))]
+        );
+    }
+
+    #[test]
+    fn ignores_global_react_without_classic_runtime() {
+        let visitor = visit_program("const Lazy = React.lazy(() => import('./lazy'))");
+        assert_eq!(visitor.jsx_pragmas, JsxPragmas::default());
+        assert!(visitor.lazy_import_sources.is_empty());
+    }
+
+    #[test]
+    fn filters_modules_in_node_modules() {
+        let paths = [
+            "app/page.tsx",
+            "node_modules/react-chartjs-2/dist/index.js",
+            "components/Chart.tsx",
+            "node_modules/@scope/editor/node_modules/lodash/index.js",
+            "packages/node_modules_utils/index.js",
+        ];
+
+        assert_eq!(
+            paths
+                .into_iter()
+                .filter(|path| !is_in_node_modules(path))
+                .collect::<Vec<_>>(),
+            vec![
+                "app/page.tsx",
+                "components/Chart.tsx",
+                "packages/node_modules_utils/index.js"
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_conflicting_specifiers() {
+        let index = [
+            (
+                RcStr::from("@/components/Chart"),
+                "src/components/Chart.tsx",
+            ),
+            (RcStr::from("./Button"), "pages/Button.tsx"),
+            (RcStr::from("lodash"), "node_modules/lodash/index.js"),
+        ];
+        let admin = [
+            (
+                RcStr::from("@/components/Chart"),
+                "admin/components/Chart.tsx",
+            ),
+            (RcStr::from("./Button"), "pages/admin/Button.tsx"),
+            (RcStr::from("lodash"), "node_modules/lodash/index.js"),
+        ];
+
+        assert_eq!(
+            conflicting_specifiers([
+                ("pages/index.tsx", &index[..]),
+                ("pages/admin/index.tsx", &admin[..]),
+            ]),
+            vec![(
+                RcStr::from("@/components/Chart"),
+                "pages/index.tsx",
+                vec!["src/components/Chart.tsx", "admin/components/Chart.tsx"]
+            )]
+        );
+    }
+
+    #[test]
+    fn detects_test_files() {
+        assert!(is_test_file("components/button.test.tsx"));
+        assert!(is_test_file("components/button.spec.ts"));
+        assert!(is_test_file("components/__tests__/button.tsx"));
+        assert!(!is_test_file("components/button.tsx"));
+        assert!(!is_test_file("components/testing.ts"));
+        assert!(!is_test_file("__tests__.ts"));
+    }
+
+    #[test]
+    fn collects_test_file_imports_separately() {
+        let visitor = visit_program_with(
+            r#"
+            import React from 'react'
+            import dynamic from 'next/dynamic'
+
+            const Hello = dynamic(() => import('./hello'))
+            const Lazy = React.lazy(() => import('./lazy'))
+            "#,
+            DynamicImportVisitor::new(true),
+        );
+
+        assert!(visitor.import_sources.is_empty());
+        assert!(visitor.lazy_import_sources.is_empty());
+        assert_eq!(visitor.test_import_sources, vec!["./hello", "./lazy"]);
+    }
+
+    #[test]
+    fn collects_retry_option() {
+        let visitor = visit_program(
+            r#"
+            import dynamic from 'next/dynamic'
+
+            const Flaky = dynamic(() => import('./flaky'), {
+                retry: { maxAttempts: 3, retriable: true },
+            })
+            const Hello = dynamic(() => import('./hello'))
+            "#,
+        );
+
+        assert_eq!(visitor.import_sources, vec!["./flaky", "./hello"]);
+        assert_eq!(
+            visitor.retry_options,
+            vec![(
+                RcStr::from("./flaky"),
+                DynamicImportRetry {
+                    max_attempts: Some(3),
+                    retriable: Some(true),
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn matches_chunk_source_maps() {
+        assert!(is_source_map_of(
+            "static/chunks/a.js",
+            "static/chunks/a.js.map"
+        ));
+        assert!(!is_source_map_of(
+            "static/chunks/a.js",
+            "static/chunks/b.js.map"
+        ));
+        assert!(!is_source_map_of(
+            "static/chunks/a.js",
+            "static/chunks/a.js"
+        ));
+    }
+
+    #[test]
+    fn fingerprints_dynamic_imports_map() {
+        let origin = RcStr::from("[project]/pages/index.js [ssr] (ecmascript)");
+        let import = |import: &str, module: &str| (RcStr::from(import), RcStr::from(module));
+        let a = import("./a", "[project]/components/a.js [client] (ecmascript)");
+        let b = import("./b", "[project]/components/b.js [client] (ecmascript)");
+
+        let fingerprint_ab = fingerprint(&origin, vec![a.clone(), b.clone()]);
+        assert_eq!(
+            fingerprint(&origin, vec![a.clone(), b.clone()]),
+            fingerprint_ab
+        );
+        assert_eq!(
+            fingerprint(&origin, vec![b.clone(), a.clone()]),
+            fingerprint_ab
+        );
+
+        assert_ne!(fingerprint(&origin, vec![a.clone()]), fingerprint_ab);
+        assert_ne!(
+            fingerprint(
+                &origin,
+                vec![
+                    a.clone(),
+                    import(
+                        "./b",
+                        "[project]/components/b/index.js [client] (ecmascript)"
+                    ),
+                ]
+            ),
+            fingerprint_ab
+        );
+        assert_ne!(
+            fingerprint(
+                &"[project]/pages/other.js [ssr] (ecmascript)".into(),
+                vec![a, b]
+            ),
+            fingerprint_ab
+        );
+    }
+
+    #[test]
+    fn builds_cross_origin_urls() {
+        assert_eq!(
+            cross_origin_url("https://static.example.com", "static/chunks/a.js"),
+            "https://static.example.com/static/chunks/a.js"
+        );
+        assert_eq!(
+            cross_origin_url("https://static.example.com/", "static/chunks/a.js"),
+            "https://static.example.com/static/chunks/a.js"
+        );
+    }
+
+    #[test]
+    fn finds_shared_chunks() {
+        let shared = shared_chunks([
+            (
+                RcStr::from("pages/a.js -> ./chart"),
+                vec!["chart.js", "vendor.js"],
+            ),
+            (
+                RcStr::from("pages/b.js -> ./table"),
+                vec!["table.js", "vendor.js"],
+            ),
+        ]);
+
+        assert_eq!(
+            shared.into_iter().collect::<Vec<_>>(),
+            vec![(
+                "vendor.js",
+                vec![
+                    "pages/a.js -> ./chart".into(),
+                    "pages/b.js -> ./table".into()
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn collects_imports_wrapped_in_iife_or_sequence() {
+        assert_eq!(
+            collect_import_sources(
+                r#"
+                import dynamic from 'next/dynamic'
+                const A = dynamic((() => import('./a'))())
+                const B = dynamic(() => (0, import('./b')))
+                const C = dynamic((0, () => import('./c')))
+                const D = dynamic(() => (void 0, (() => import('./d'))()))
+                "#
+            ),
+            vec!["./a", "./b", "./c", "./d"]
+        );
+    }
+
+    #[test]
+    fn does_not_match_create_context() {
+        assert_eq!(
+            collect_import_sources(
+                r#"
+                import { createContext } from 'react'
+                import dynamic from 'next/dynamic'
+
+                const ThemeContext = createContext(null)
+                const Config = createContext(() => import('./config'))
+                const Provider = dynamic(() => import('./ThemeProvider'))
+
+                export default function App({ children }) {
+                    return (
+                        <ThemeContext.Provider value={<Provider />}>
+                            {children}
+                        </ThemeContext.Provider>
+                    )
+                }
+                "#
+            ),
+            vec!["./ThemeProvider"]
+        );
+    }
+
+    #[test]
+    fn collects_css_imports() {
+        assert_eq!(
+            collect_import_sources(
+                r#"
+                import dynamic from 'next/dynamic'
+                const Theme = dynamic(() => import('./theme.css'), { ssr: false })
+                "#
+            ),
+            vec!["./theme.css"]
+        );
+    }
+
+    #[test]
+    fn skips_imports_which_cant_be_analyzed() {
+        // A variable, an expression which isn't a literal and a template with expressions, also
+        // when wrapped in other calls.
+        assert_eq!(
+            collect_import_sources(
+                r#"
+                import dynamic from 'next/dynamic'
+                const path = './hello'
+                const Hello = dynamic(() => import(path))
+                const World = dynamic(() => import(getPath('world')))
+                const Other = dynamic(() => import('./' + name))
+                const Named = dynamic(() => import(`./${name}`).then((mod) => mod.Named))
+                "#
+            ),
+            Vec::<RcStr>::new()
+        );
+        assert_eq!(
+            collect_import_sources(
+                r#"
+                import dynamic from 'next/dynamic'
+                const Hello = dynamic(() => import(`./hello`).then((mod) => mod.Hello))
+                const World = dynamic(() => import('./' + 'world'))
+                "#
+            ),
+            vec!["./hello"]
+        );
+    }
+
+    #[test]
+    fn scopes_modules_to_reaching_entries() {
+        let scopes = scopes_by_module([
+            (RcStr::from("pages/a.js"), vec!["layout", "chart"]),
+            (RcStr::from("pages/b.js"), vec!["layout"]),
+            (RcStr::from("pages/a.js"), vec!["chart"]),
+        ]);
+
+        assert_eq!(
+            scopes.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("layout", vec!["pages/a.js".into(), "pages/b.js".into()]),
+                ("chart", vec!["pages/a.js".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn derives_router_from_layer() {
+        for layer in ["app-rsc", "app-ssr", "app-edge-ssr"] {
+            assert_eq!(dynamic_import_router(layer), LoadableRouter::App);
+        }
+        for layer in ["ssr", "edge-ssr", "api"] {
+            assert_eq!(dynamic_import_router(layer), LoadableRouter::Pages);
+        }
+    }
+
+    #[test]
+    fn stops_at_max_depth() {
+        let code = r#"
+            import dynamic from 'next/dynamic'
+
+            const Shallow = wrap(dynamic(() => import('./shallow')))
+            const Deep = wrap(wrap(wrap(dynamic(() => import('./deep')))))
+            "#;
+
+        let visitor = visit_program_with(code, DynamicImportVisitor::new(false).with_max_depth(2));
+        assert_eq!(visitor.import_sources, vec!["./shallow"]);
+        assert!(visitor.max_depth_exceeded);
+
+        assert!(!visit_program(code).max_depth_exceeded);
+    }
+
+    #[test]
+    fn does_not_count_jsx_factory_calls_towards_max_depth() {
+        let code = r#"
+            import dynamic from 'next/dynamic'
+            import { jsx as _jsx } from 'react/jsx-runtime'
+
+            const Page = () => _jsx(Layout, {
+                children: _jsx(Section, {
+                    children: React.createElement(Card, {
+                        render: () => wrap(dynamic(() => import('./card'))),
+                    }),
+                }),
+            })
+            "#;
+
+        let visitor = visit_program_with(code, DynamicImportVisitor::new(false).with_max_depth(2));
+        assert_eq!(visitor.import_sources, vec!["./card"]);
+        assert!(!visitor.max_depth_exceeded);
+    }
+
+    #[test]
+    fn rejects_invalid_dynamic_import_ids() {
+        assert_eq!(
+            invalid_dynamic_import_id("[project]/pages/índex.js", "./hello"),
+            None
+        );
+        assert!(invalid_dynamic_import_id("[project]/pages/\u{FFFD}.js", "./hello").is_some());
+        assert!(invalid_dynamic_import_id("[project]/pages/index.js", "./hel\nlo").is_some());
+        assert!(invalid_dynamic_import_id("[project]/pages/index.js", "").is_some());
+    }
+
+    #[test]
+    fn dynamic_import_id_joins_origin_and_import() {
+        assert_eq!(
+            dynamic_import_id("[project]/pages/index.js", "../components/hello"),
+            "[project]/pages/index.js -> ../components/hello"
+        );
+        assert_eq!(
+            dynamic_import_key("[project]/pages/index.js", "../components/hello", "#"),
+            "[project]/pages/index.js#../components/hello"
+        );
+    }
+
+    #[tokio::test]
+    async fn fingerprints_equivalent_chunk_groups_alike() {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = Vc::upcast::<Box<dyn FileSystem>>(VirtualFileSystem::new()).root();
+            let index = test_module(root, "ssr", "pages/index.js", "").await?;
+            let about = test_module(root, "ssr", "pages/about.js", "").await?;
+            let chunks = |files: &'static [&'static str]| async move {
+                let chunks = files
+                    .iter()
+                    .map(|file| async move {
+                        Ok(ResolvedVc::upcast(
+                            turbopack_core::virtual_output::VirtualOutputAsset::new(
+                                root.join(format!("out/{file}").into()),
+                                AssetContent::file(File::from("").into()),
+                            )
+                            .to_resolved()
+                            .await?,
+                        ))
+                    })
+                    .try_join()
+                    .await?;
+                anyhow::Ok(ResolvedVc::<OutputAssets>::cell(chunks))
+            };
+            let a = chunks(&["a.js", "shared.js"]).await?;
+            let b = chunks(&["b.js"]).await?;
+
+            let fingerprint =
+                |entries: Vec<(ResolvedVc<Box<dyn Module>>, DynamicImportedOutputAssets)>| {
+                    dynamic_imported_chunks_fingerprint(Vc::cell(FxIndexMap::from_iter(entries)))
+                };
+            let key = fingerprint(vec![
+                (index, vec![("./a".into(), a), ("./b".into(), b)]),
+                (about, vec![("./b".into(), b)]),
+            ])
+            .await?;
+            assert_eq!(
+                *fingerprint(vec![
+                    (about, vec![("./b".into(), b)]),
+                    (index, vec![("./b".into(), b), ("./a".into(), a)]),
+                ])
+                .await?,
+                *key
+            );
+
+            let reordered_a = chunks(&["shared.js", "a.js"]).await?;
+            assert_ne!(
+                *fingerprint(vec![
+                    (index, vec![("./a".into(), reordered_a), ("./b".into(), b)]),
+                    (about, vec![("./b".into(), b)]),
+                ])
+                .await?,
+                *key
+            );
+            assert_ne!(
+                *fingerprint(vec![(index, vec![("./a".into(), a), ("./b".into(), b)])]).await?,
+                *key
+            );
+
+            anyhow::Ok(())
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn collects_async_loader_ids_next_to_module_ids() {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = Vc::upcast::<Box<dyn FileSystem>>(VirtualFileSystem::new()).root();
+            let origin = test_module(
+                root,
+                "ssr",
+                "pages/index.js",
+                "import dynamic from 'next/dynamic'",
+            )
+            .await?;
+            let chart =
+                test_module(root, "client", "components/chart.js", "export default 1").await?;
+            let chunking_context = browser_chunking_context(root).await?;
+            let dynamic_imports = Vc::<DynamicImports>::cell(FxIndexMap::from_iter([(
+                origin,
+                vec![("../components/chart".into(), chart)],
+            )]));
+
+            let ids = collect_chunk_item_ids(chunking_context, dynamic_imports, true).await?;
+            let [(import, ids)] = &ids[&origin][..] else {
+                panic!("expected one dynamic import");
+            };
+            assert_eq!(import, "../components/chart");
+            let ids = ids.await?;
+            let async_loader_id = ids.async_loader_id.expect("async loader id").await?;
+            let module_id = ids.module_id.await?;
+            assert_eq!(
+                *module_id,
+                *chunking_context
+                    .chunk_item_id_from_ident(chart.ident())
+                    .await?
+            );
+            assert_ne!(*async_loader_id, *module_id);
+
+            let ids = collect_chunk_item_ids(chunking_context, dynamic_imports, false).await?;
+            assert!(ids[&origin][0].1.await?.async_loader_id.is_none());
+
+            anyhow::Ok(())
+        })
+        .await
+        .unwrap();
+    }
 }
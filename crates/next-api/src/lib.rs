@@ -23,6 +23,13 @@ mod server_actions;
 mod versioned_content_map;
 mod webpack_stats;
 
+pub use dynamic_imports::{DynamicImportScopes, DynamicImportedChunks};
+pub use loadable_manifest::{
+    create_react_loadable_manifest_with_progress, ContentHashFileAddress, EmptyLoadableEntries,
+    LoadableManifestOptions, LoadableManifestProgress, LoadableManifestVersion,
+    ManifestFileAddress, ManifestSerializer,
+};
+
 // Declare build-time information variables generated in build.rs
 shadow_rs::shadow!(build);
 
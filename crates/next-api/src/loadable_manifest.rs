@@ -1,74 +1,1654 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use anyhow::Result;
-use next_core::next_manifests::LoadableManifest;
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use next_core::next_manifests::{
+    LoadableManifest, LoadableManifestFiles, LoadableRetry, LoadableRouter, ManifestFile,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384};
+use tracing::Instrument;
 use turbo_rcstr::RcStr;
-use turbo_tasks::{ResolvedVc, TryFlatJoinIterExt, Vc};
+use turbo_tasks::{
+    trace::TraceRawVcs, FxIndexMap, NonLocalValue, ResolvedVc, TryFlatJoinIterExt, TryJoinIterExt,
+    Vc,
+};
 use turbo_tasks_fs::{File, FileContent, FileSystemPath};
+use turbo_tasks_hash::{encode_hex, hash_xxh3_hash64};
 use turbopack_core::{
-    asset::AssetContent,
-    module::Module,
+    asset::{Asset, AssetContent},
     output::{OutputAsset, OutputAssets},
     virtual_output::VirtualOutputAsset,
 };
 
-use crate::dynamic_imports::DynamicImportedChunks;
+use crate::dynamic_imports::{
+    dynamic_import_id, dynamic_import_key, dynamic_import_retry_options, dynamic_import_router,
+    resolve_dynamic_imported_chunk_files, resolve_dynamic_imported_chunk_files_cross_origin,
+    DynamicImportScopes, DynamicImportedChunks,
+};
+
+/// Opt-in variations of the emitted `react-loadable-manifest.json`. The default matches what the
+/// Next.js runtime reads.
+#[turbo_tasks::value(shared)]
+#[derive(Clone, Default)]
+pub struct LoadableManifestOptions {
+    /// Record the content hash of every file next to its path, for runtimes that identify chunks
+    /// by content for immutable caching. Off by default because it reads every chunk.
+    pub include_content_hashes: bool,
+    /// Emit the manifest at `react-loadable-manifest.<hash>.json`, named after the first 8 hex
+    /// digits of the SHA-256 of its content, so stale CDN caches can't serve an outdated manifest.
+    /// The original path then holds a pointer to it: `{ "hash": "<hash>" }`.
+    pub content_address: bool,
+    /// The shape of the entries' `files`, see [LoadableManifestVersion].
+    pub version: LoadableManifestVersion,
+    /// Serializes the manifest of [create_react_loadable_manifest] instead of the default pretty
+    /// printed JSON.
+    pub serializer: Option<ResolvedVc<Box<dyn ManifestSerializer>>>,
+    /// Scope every entry to the entrypoints reaching the module containing the dynamic import, see
+    /// `ReducedGraphs::get_next_dynamic_import_scopes`. Without it, entries are global.
+    pub scopes: Option<ResolvedVc<DynamicImportScopes>>,
+    /// Reference every file by the address it is stored at instead of its path relative to the
+    /// client output, see [ManifestFileAddress].
+    pub file_address: Option<ResolvedVc<Box<dyn ManifestFileAddress>>>,
+    /// The base URL of chunks hosted on another origin than the pages, e.g.
+    /// `https://static.example.com`. Files outside of the client output are referenced by their
+    /// path prefixed with it instead of being left out.
+    pub cross_origin_base_url: Option<RcStr>,
+    /// What to do with the entries of dynamic imports contributing no chunks of their own, see
+    /// [EmptyLoadableEntries].
+    pub empty_entries: EmptyLoadableEntries,
+    /// The separator between the origin and the import in the keys and ids of the entries, ` -> `
+    /// by default. The Next.js runtime looks entries up by the ids injected into `dynamic()` calls
+    /// by the next_dynamic transform, which use the default, so only set it for other runtimes.
+    pub key_separator: Option<RcStr>,
+    /// The maximum number of files of an entry. The files beyond it are left out and counted in
+    /// the `overflow` of the entry, guarding against runaway entries from misconfigured chunking.
+    /// No maximum by default.
+    pub max_files_per_entry: Option<usize>,
+    /// Emit `react-loadable-integrity.json` next to the manifest, mapping the files of every entry
+    /// to their SHA-384 subresource integrity, keyed like the manifest. It is a separate file so
+    /// the manifest stays small for runtimes without a CSP requiring integrity. Off by default
+    /// because it reads every chunk. Only [create_react_loadable_manifest] emits it.
+    pub integrity_manifest: bool,
+    /// Add the OpenTelemetry trace context of the manifest creation to the manifest, as
+    /// `"__otel": { "traceId": "...", "spanId": "..." }`, so build systems can correlate it with
+    /// their trace. The trace is the one propagated to the build by the W3C `TRACEPARENT`
+    /// environment variable, the field is left out without it. Only
+    /// [create_react_loadable_manifest] emits it.
+    pub emit_trace_context: bool,
+    /// Record in every entry whether the module containing the dynamic import belongs to the app
+    /// or the pages router, as `"router": "app" | "pages"`, so hybrid apps can tell the entries of
+    /// both routers apart. Off by default to keep the manifest as read by the Next.js runtime.
+    pub include_router: bool,
+}
+
+/// The `__otel` field of the loadable manifest, see [LoadableManifestOptions::emit_trace_context].
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct TraceContext {
+    trace_id: RcStr,
+    span_id: RcStr,
+}
+
+impl TraceContext {
+    /// The trace context of a span within the trace of a W3C `traceparent`, i.e.
+    /// `00-<trace id>-<parent span id>-<flags>`. The span is the one with `span_id` if the span
+    /// is recorded, otherwise the parent span.
+    fn from_traceparent(traceparent: &str, span_id: Option<u64>) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let (Some("00"), Some(trace_id), Some(parent_id), Some(_flags), None) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            return None;
+        };
+        let is_hex_id = |id: &str, len: usize| {
+            id.len() == len
+                && id
+                    .bytes()
+                    .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+                && id.bytes().any(|byte| byte != b'0')
+        };
+        if !is_hex_id(trace_id, 32) || !is_hex_id(parent_id, 16) {
+            return None;
+        }
+        Some(TraceContext {
+            trace_id: trace_id.into(),
+            span_id: match span_id {
+                Some(span_id) => format!("{span_id:016x}").into(),
+                None => parent_id.into(),
+            },
+        })
+    }
+}
+
+/// A loadable manifest with its trace context, see [LoadableManifestOptions::emit_trace_context].
+#[derive(Serialize)]
+struct LoadableManifestWithTraceContext<'a, M> {
+    #[serde(rename = "__otel", skip_serializing_if = "Option::is_none")]
+    trace_context: Option<&'a TraceContext>,
+    #[serde(flatten)]
+    loadable_manifest: &'a M,
+}
+
+/// The files of every entry of the loadable manifest, by key, with their subresource integrity.
+type LoadableIntegrityManifest = BTreeMap<RcStr, BTreeMap<RcStr, RcStr>>;
+
+/// Serializes the loadable manifest for deployment targets which need a custom format, e.g. with
+/// sorted keys or without null values.
+#[turbo_tasks::value_trait]
+pub trait ManifestSerializer {
+    /// Receives the manifest as compact JSON and returns the content to emit.
+    fn serialize(self: Vc<Self>, manifest: RcStr) -> Vc<FileContent>;
+}
+
+/// Maps the files of the manifest entries to their addresses, for deployments storing assets
+/// content-addressed rather than at their output path, see [ContentHashFileAddress].
+#[turbo_tasks::value_trait]
+pub trait ManifestFileAddress {
+    /// Receives the file and its path relative to the client output, and returns the address to
+    /// reference it by.
+    fn address(self: Vc<Self>, file: Vc<Box<dyn OutputAsset>>, path: RcStr) -> Vc<RcStr>;
+}
+
+/// Addresses files by the hash of their content, as `{prefix}{hash}.{extension}`.
+#[turbo_tasks::value(shared)]
+pub struct ContentHashFileAddress {
+    pub prefix: RcStr,
+}
+
+#[turbo_tasks::value_impl]
+impl ManifestFileAddress for ContentHashFileAddress {
+    #[turbo_tasks::function]
+    async fn address(&self, file: Vc<Box<dyn OutputAsset>>, path: RcStr) -> Result<Vc<RcStr>> {
+        let hash = output_asset_content_hash(file).await?;
+        Ok(Vc::cell(content_hash_address(&self.prefix, &hash, &path)))
+    }
+}
+
+fn content_hash_address(prefix: &str, hash: &str, path: &str) -> RcStr {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    match file_name.rsplit_once('.') {
+        Some((_, extension)) => format!("{prefix}{hash}.{extension}").into(),
+        None => format!("{prefix}{hash}").into(),
+    }
+}
+
+/// Version 1 lists the paths of the files of an entry, which is what the Next.js runtime reads.
+/// Version 2 lists [ManifestFile]s instead, which carry optional metadata next to the path.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs, NonLocalValue,
+)]
+pub enum LoadableManifestVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+/// The handling of entries without files, for dynamically imported modules fully contained in the
+/// chunks of the importing module.
+///
+/// They are kept by default, like the webpack plugin does: the runtime still records the ids of
+/// the rendered dynamic imports for the client to wait for, and loads no files for them.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs, NonLocalValue,
+)]
+pub enum EmptyLoadableEntries {
+    /// Emit them with empty `files`.
+    #[default]
+    Keep,
+    /// Leave them out of the manifest.
+    Omit,
+    /// Emit them with empty `files` and `"empty": true`.
+    Mark,
+}
+
+impl EmptyLoadableEntries {
+    /// Applies the handling to an entry, returning `None` if it is to be left out.
+    fn apply(self, mut entry: LoadableManifest) -> Option<LoadableManifest> {
+        if !entry.files.is_empty() {
+            return Some(entry);
+        }
+        match self {
+            EmptyLoadableEntries::Keep => {}
+            EmptyLoadableEntries::Omit => return None,
+            EmptyLoadableEntries::Mark => entry.empty = Some(true),
+        }
+        Some(entry)
+    }
+}
+
+/// Receives the progress of building a loadable manifest, e.g. to render a progress bar.
+pub trait LoadableManifestProgress: Send + Sync {
+    /// Called once per manifest entry, with the number of entries so far and the total.
+    fn entry(&self, count: usize, total: usize);
+}
 
 #[turbo_tasks::function]
 pub async fn create_react_loadable_manifest(
     dynamic_import_entries: Vc<DynamicImportedChunks>,
     client_relative_path: Vc<FileSystemPath>,
     output_path: Vc<FileSystemPath>,
+    options: Vc<LoadableManifestOptions>,
 ) -> Result<Vc<OutputAssets>> {
-    let dynamic_import_entries = &*dynamic_import_entries.await?;
+    react_loadable_manifest(
+        dynamic_import_entries,
+        client_relative_path,
+        output_path,
+        options,
+        None,
+    )
+    .await
+}
+
+/// Like [create_react_loadable_manifest], but reports its progress to `progress`. This isn't a
+/// turbo-tasks function, as a trait object can't be a task input, so it has to be called from
+/// within a task and isn't cached by itself.
+pub async fn create_react_loadable_manifest_with_progress(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+    client_relative_path: Vc<FileSystemPath>,
+    output_path: Vc<FileSystemPath>,
+    options: Vc<LoadableManifestOptions>,
+    progress: &dyn LoadableManifestProgress,
+) -> Result<Vc<OutputAssets>> {
+    react_loadable_manifest(
+        dynamic_import_entries,
+        client_relative_path,
+        output_path,
+        options,
+        Some(progress),
+    )
+    .await
+}
+
+async fn react_loadable_manifest(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+    client_relative_path: Vc<FileSystemPath>,
+    output_path: Vc<FileSystemPath>,
+    options: Vc<LoadableManifestOptions>,
+    progress: Option<&dyn LoadableManifestProgress>,
+) -> Result<Vc<OutputAssets>> {
+    // The attributes follow the OpenTelemetry semantic conventions, for subscribers exporting the
+    // spans of the build to it.
+    let span = tracing::info_span!(
+        "create react loadable manifest",
+        "otel.kind" = "INTERNAL",
+        "code.function" = "create_react_loadable_manifest"
+    );
+    let span_id = span.id().map(|id| id.into_u64());
+    async move {
+        let mut output = dynamic_import_output_assets(dynamic_import_entries).await?;
+        let with_integrity = options.await?.integrity_manifest;
+        let (loadable_manifest, integrity_manifest) = loadable_manifest_entries_with_integrity(
+            dynamic_import_entries,
+            client_relative_path,
+            options,
+            progress,
+            with_integrity,
+        )
+        .await?;
+        let options = options.await?;
+        let trace_context = if options.emit_trace_context {
+            std::env::var("TRACEPARENT")
+                .ok()
+                .and_then(|traceparent| TraceContext::from_traceparent(&traceparent, span_id))
+        } else {
+            None
+        };
+        if let Some(integrity_manifest) = integrity_manifest {
+            output.push(
+                loadable_manifest_asset(
+                    output_path
+                        .parent()
+                        .join("react-loadable-integrity.json".into()),
+                    &integrity_manifest,
+                )
+                .await?,
+            );
+        }
+        if options.content_address {
+            // Sorted, so that the hash only changes together with the content.
+            let loadable_manifest = loadable_manifest.into_iter().collect::<BTreeMap<_, _>>();
+            let content = manifest_content(
+                &LoadableManifestWithTraceContext {
+                    trace_context: trace_context.as_ref(),
+                    loadable_manifest: &loadable_manifest,
+                },
+                options.serializer,
+            )?;
+            let FileContent::Content(file) = &*content.await? else {
+                bail!("the loadable manifest serializer returned no content");
+            };
+            let hash = content_address(file.content().to_bytes()?);
+            output.push(
+                manifest_asset(
+                    output_path.append_to_stem(format!(".{hash}").into()),
+                    content,
+                )
+                .await?,
+            );
+            output.push(
+                loadable_manifest_asset(output_path, &serde_json::json!({ "hash": hash })).await?,
+            );
+        } else {
+            output.push(
+                manifest_asset(
+                    output_path,
+                    manifest_content(
+                        &LoadableManifestWithTraceContext {
+                            trace_context: trace_context.as_ref(),
+                            loadable_manifest: &loadable_manifest,
+                        },
+                        options.serializer,
+                    )?,
+                )
+                .await?,
+            );
+        }
+        Ok(Vc::cell(output))
+    }
+    .instrument(span)
+    .await
+}
+
+/// Like [create_react_loadable_manifest], but distributes the entries across `shard_count`
+/// manifests next to `output_path` (`react-loadable-manifest.{shard}.json`), so CDNs can cache
+/// them independently and the runtime only needs to load the shards of the current page's dynamic
+/// imports. The shard of an entry is the xxh3 64-bit hash of its id modulo `shard_count`. Every
+/// shard is emitted, even if empty.
+#[turbo_tasks::function]
+pub async fn create_react_loadable_manifest_sharded(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+    client_relative_path: Vc<FileSystemPath>,
+    output_path: Vc<FileSystemPath>,
+    shard_count: u8,
+    options: Vc<LoadableManifestOptions>,
+) -> Result<Vc<OutputAssets>> {
+    if shard_count == 0 {
+        bail!("shard_count must be greater than 0");
+    }
+
+    let mut output = dynamic_import_output_assets(dynamic_import_entries).await?;
+
+    let mut shards: Vec<HashMap<RcStr, LoadableManifest>> =
+        (0..shard_count).map(|_| HashMap::new()).collect();
+    for (id, manifest_item) in
+        loadable_manifest_entries(dynamic_import_entries, client_relative_path, options, None)
+            .await?
+    {
+        shards[shard_index(&id, shard_count)].insert(id, manifest_item);
+    }
+
+    for (shard, loadable_manifest) in shards.iter().enumerate() {
+        output.push(
+            loadable_manifest_asset(
+                output_path.append_to_stem(format!(".{shard}").into()),
+                loadable_manifest,
+            )
+            .await?,
+        );
+    }
+
+    Ok(Vc::cell(output))
+}
 
+/// Emits the same manifest as [create_react_loadable_manifest] encoded as MessagePack, next to
+/// `output_path` with an additional `.msgpack` extension. Maps are encoded with their field names,
+/// so it decodes to the same value as the JSON manifest. The chunks are not part of the output,
+/// they are emitted with the JSON manifest.
+#[turbo_tasks::function]
+pub async fn create_react_loadable_manifest_msgpack(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+    client_relative_path: Vc<FileSystemPath>,
+    output_path: Vc<FileSystemPath>,
+    options: Vc<LoadableManifestOptions>,
+) -> Result<Vc<Box<dyn OutputAsset>>> {
+    let loadable_manifest =
+        loadable_manifest_entries(dynamic_import_entries, client_relative_path, options, None)
+            .await?;
+
+    Ok(Vc::upcast(VirtualOutputAsset::new(
+        output_path.append(".msgpack".into()),
+        AssetContent::file(
+            FileContent::Content(File::from(rmp_serde::to_vec_named(&loadable_manifest)?)).cell(),
+        ),
+    )))
+}
+
+/// Emits an alternative to the loadable manifest for runtime integrations looking chunks up by the
+/// import specifier as written rather than by dynamic import id: an object mapping each origin
+/// module to an object mapping its specifiers to their files, `{ [origin]: { [specifier]: files }
+/// }`.
+#[turbo_tasks::function]
+pub async fn create_specifier_loadable_manifest(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+    client_relative_path: Vc<FileSystemPath>,
+    output_path: Vc<FileSystemPath>,
+) -> Result<Vc<Box<dyn OutputAsset>>> {
+    let client_relative_path = &*client_relative_path.await?;
+    let entries = dynamic_import_entries
+        .await?
+        .iter()
+        .map(|(origin, dynamic_imports)| async move {
+            let origin_path = &*origin.ident().path().await?;
+            let imports = dynamic_imports
+                .iter()
+                .map(|(specifier, chunk_output)| async move {
+                    let files = chunk_output
+                        .await?
+                        .iter()
+                        .map(|file| async move {
+                            Ok(client_relative_path
+                                .get_path_to(&*file.ident().path().await?)
+                                .map(RcStr::from))
+                        })
+                        .try_flat_join()
+                        .await?;
+                    Ok((specifier.clone(), files))
+                })
+                .try_join()
+                .await?;
+            Ok((RcStr::from(origin_path.to_string()), imports))
+        })
+        .try_join()
+        .await?;
+
+    Ok(*loadable_manifest_asset(output_path, &specifier_manifest(entries)).await?)
+}
+
+/// Origins with the same path, e.g. the same module in different layers, are merged.
+fn specifier_manifest(
+    entries: Vec<(RcStr, Vec<(RcStr, Vec<RcStr>)>)>,
+) -> FxIndexMap<RcStr, FxIndexMap<RcStr, Vec<RcStr>>> {
+    let mut manifest: FxIndexMap<RcStr, FxIndexMap<RcStr, Vec<RcStr>>> = FxIndexMap::default();
+    for (origin_path, imports) in entries {
+        manifest.entry(origin_path).or_default().extend(imports);
+    }
+    manifest
+}
+
+/// Returns the deduplicated output assets of all files referenced by the loadable manifest created
+/// for the same arguments, e.g. to check that none of them are orphaned or missing from the output.
+#[turbo_tasks::function]
+pub async fn loadable_manifest_output_assets(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+    client_relative_path: Vc<FileSystemPath>,
+) -> Result<Vc<OutputAssets>> {
+    let chunk_files =
+        resolve_dynamic_imported_chunk_files(dynamic_import_entries, client_relative_path).await?;
+    Ok(Vc::cell(
+        referenced_files(&chunk_files).into_values().collect(),
+    ))
+}
+
+/// The files referenced by the manifest entries, keyed by their path and in order of first
+/// reference.
+fn referenced_files<T: Copy>(chunk_files: &[(RcStr, Vec<(RcStr, T)>)]) -> FxIndexMap<RcStr, T> {
+    chunk_files
+        .iter()
+        .flat_map(|(_, files)| files)
+        .map(|(path, file)| (path.clone(), *file))
+        .collect()
+}
+
+/// All chunks referenced by the dynamic imports, which need to be emitted alongside the manifest.
+async fn dynamic_import_output_assets(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+) -> Result<Vec<ResolvedVc<Box<dyn OutputAsset>>>> {
     let mut output = vec![];
+    for dynamic_imports in dynamic_import_entries.await?.values() {
+        for (_, chunk_output) in dynamic_imports {
+            output.extend(chunk_output.await?.iter().copied());
+        }
+    }
+    Ok(output)
+}
+
+async fn loadable_manifest_entries(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+    client_relative_path: Vc<FileSystemPath>,
+    options: Vc<LoadableManifestOptions>,
+    progress: Option<&dyn LoadableManifestProgress>,
+) -> Result<HashMap<RcStr, LoadableManifest>> {
+    Ok(loadable_manifest_entries_with_integrity(
+        dynamic_import_entries,
+        client_relative_path,
+        options,
+        progress,
+        false,
+    )
+    .await?
+    .0)
+}
+
+/// Like [loadable_manifest_entries], but also returns the integrity manifest of the entries
+/// `with_integrity`, see [LoadableManifestOptions::integrity_manifest].
+async fn loadable_manifest_entries_with_integrity(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+    client_relative_path: Vc<FileSystemPath>,
+    options: Vc<LoadableManifestOptions>,
+    progress: Option<&dyn LoadableManifestProgress>,
+    with_integrity: bool,
+) -> Result<(
+    HashMap<RcStr, LoadableManifest>,
+    Option<LoadableIntegrityManifest>,
+)> {
+    let options = options.await?;
+    let include_content_hashes = options.include_content_hashes;
+
+    let retry_options = retry_options_by_id(dynamic_import_entries).await?;
+    let routers = if options.include_router {
+        routers_by_id(dynamic_import_entries).await?
+    } else {
+        HashMap::new()
+    };
+    let keys = match &options.key_separator {
+        Some(separator) => keys_by_id(dynamic_import_entries, separator).await?,
+        None => HashMap::new(),
+    };
+    let scopes = match options.scopes {
+        Some(scopes) => scopes_by_id(dynamic_import_entries, *scopes).await?,
+        None => HashMap::new(),
+    };
+
+    let chunk_files = match &options.cross_origin_base_url {
+        Some(base_url) => {
+            resolve_dynamic_imported_chunk_files_cross_origin(
+                dynamic_import_entries,
+                client_relative_path,
+                base_url.clone(),
+            )
+            .await?
+        }
+        None => {
+            resolve_dynamic_imported_chunk_files(dynamic_import_entries, client_relative_path)
+                .await?
+        }
+    };
+
     let mut loadable_manifest: HashMap<RcStr, LoadableManifest> = Default::default();
+    let mut integrity_manifest = with_integrity.then(LoadableIntegrityManifest::new);
+    for (id, files) in with_progress(&chunk_files, progress) {
+        let (files, overflow) = cap_files(files, options.max_files_per_entry);
+        let content_hashes = if include_content_hashes {
+            Some(
+                files
+                    .iter()
+                    .map(|(_, file)| output_asset_content_hash(**file))
+                    .try_join()
+                    .await?,
+            )
+        } else {
+            None
+        };
+
+        let paths = match options.file_address {
+            Some(file_address) => {
+                files
+                    .iter()
+                    .map(|(path, file)| async move {
+                        Ok(file_address
+                            .address(**file, path.clone())
+                            .await?
+                            .clone_value())
+                    })
+                    .try_join()
+                    .await?
+            }
+            None => files.iter().map(|(path, _)| path.clone()).collect(),
+        };
+
+        let integrity = if integrity_manifest.is_some() {
+            Some(
+                files
+                    .iter()
+                    .map(|(_, file)| output_asset_integrity(**file))
+                    .try_join()
+                    .await?,
+            )
+        } else {
+            None
+        };
+
+        let key = keys.get(id).unwrap_or(id);
+        let manifest_item = LoadableManifest {
+            id: key.clone(),
+            files: manifest_files(paths.iter().cloned(), options.version),
+            content_hashes,
+            retry: retry_options.get(id).cloned(),
+            router: routers.get(id).copied(),
+            scope: scopes.get(id).cloned(),
+            empty: None,
+            overflow,
+        };
+
+        if let Some(manifest_item) = options.empty_entries.apply(manifest_item) {
+            loadable_manifest.insert(key.clone(), manifest_item);
+            if let (Some(integrity_manifest), Some(integrity)) =
+                (&mut integrity_manifest, integrity)
+            {
+                integrity_manifest.insert(key.clone(), paths.into_iter().zip(integrity).collect());
+            }
+        }
+    }
+
+    Ok((loadable_manifest, integrity_manifest))
+}
+
+/// Keeps the first `max_files` files, returning them with the number of files left out, if any.
+fn cap_files<T>(files: &[T], max_files: Option<usize>) -> (&[T], Option<usize>) {
+    match max_files {
+        Some(max_files) if files.len() > max_files => {
+            (&files[..max_files], Some(files.len() - max_files))
+        }
+        _ => (files, None),
+    }
+}
+
+fn manifest_files(
+    paths: impl Iterator<Item = RcStr>,
+    version: LoadableManifestVersion,
+) -> LoadableManifestFiles {
+    match version {
+        LoadableManifestVersion::V1 => LoadableManifestFiles::Paths(paths.collect()),
+        LoadableManifestVersion::V2 => LoadableManifestFiles::Files(
+            paths
+                .map(|path| ManifestFile {
+                    kind: manifest_file_kind(&path),
+                    preload_as: preload_as(&path),
+                    path,
+                    ..Default::default()
+                })
+                .collect(),
+        ),
+    }
+}
 
-    for (origin, dynamic_imports) in dynamic_import_entries.into_iter() {
-        let origin_path = &*origin.ident().path().await?;
+/// The kind of a manifest file by its extension, so the runtime can preload stylesheets of e.g. a
+/// dynamically imported theme (`import('./theme.css')`), whose chunk group has no JavaScript.
+fn manifest_file_kind(path: &str) -> Option<RcStr> {
+    match path.rsplit_once('.')?.1 {
+        "js" => Some("js".into()),
+        "css" => Some("css".into()),
+        _ => None,
+    }
+}
 
-        for (import, chunk_output) in dynamic_imports {
-            let chunk_output = chunk_output.await?;
-            output.extend(chunk_output.iter().copied());
+/// The destination of a preload of a manifest file by its extension, i.e. the value of the `as`
+/// attribute of `<link rel="preload">`, so the runtime emits hints the browser doesn't discard.
+fn preload_as(path: &str) -> Option<RcStr> {
+    let preload_as = match path.rsplit_once('.')?.1 {
+        "js" | "mjs" => "script",
+        "css" => "style",
+        "wasm" | "json" => "fetch",
+        "woff" | "woff2" | "ttf" | "otf" | "eot" => "font",
+        "avif" | "gif" | "jpg" | "jpeg" | "png" | "svg" | "webp" => "image",
+        _ => return None,
+    };
+    Some(preload_as.into())
+}
 
-            let id: RcStr = format!("{} -> {}", origin_path, import).into();
+/// Iterates `items`, reporting every item to `progress`.
+fn with_progress<'a, T>(
+    items: &'a [T],
+    progress: Option<&'a dyn LoadableManifestProgress>,
+) -> impl Iterator<Item = &'a T> {
+    let total = items.len();
+    items.iter().enumerate().map(move |(index, item)| {
+        if let Some(progress) = progress {
+            progress.entry(index + 1, total);
+        }
+        item
+    })
+}
 
-            let client_relative_path_value = client_relative_path.await?;
-            let files = chunk_output
+/// Returns the `retry` options of the dynamic imports, keyed by the id of the dynamic import.
+async fn retry_options_by_id(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+) -> Result<HashMap<RcStr, LoadableRetry>> {
+    Ok(dynamic_import_entries
+        .await?
+        .keys()
+        .map(|&origin| async move {
+            let origin_path = &*origin.ident().path().await?;
+            dynamic_import_retry_options(*origin)
+                .await?
                 .iter()
-                .map(move |&file| {
-                    let client_relative_path_value = client_relative_path_value.clone();
-                    async move {
-                        Ok(client_relative_path_value
-                            .get_path_to(&*file.ident().path().await?)
-                            .map(|path| path.into()))
-                    }
+                .map(|(import, retry)| async move {
+                    let retry = retry.await?;
+                    Ok((
+                        dynamic_import_id(origin_path, import),
+                        LoadableRetry {
+                            max_attempts: retry.max_attempts,
+                            retriable: retry.retriable,
+                        },
+                    ))
                 })
-                .try_flat_join()
-                .await?;
+                .try_join()
+                .await
+        })
+        .try_flat_join()
+        .await?
+        .into_iter()
+        .collect())
+}
 
-            let manifest_item = LoadableManifest {
-                id: id.clone(),
-                files,
+/// Returns the router of the origin of the dynamic imports, keyed by the id of the dynamic import.
+async fn routers_by_id(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+) -> Result<HashMap<RcStr, LoadableRouter>> {
+    Ok(dynamic_import_entries
+        .await?
+        .iter()
+        .map(|(origin, dynamic_imports)| async move {
+            let origin_ident = origin.ident().await?;
+            let Some(layer) = origin_ident.layer else {
+                return Ok(vec![]);
             };
+            let router = dynamic_import_router(&*layer.await?);
+            let origin_path = &*origin_ident.path.await?;
+            Ok(dynamic_imports
+                .iter()
+                .map(|(import, _)| (dynamic_import_id(origin_path, import), router))
+                .collect())
+        })
+        .try_flat_join()
+        .await?
+        .into_iter()
+        .collect())
+}
+
+/// Returns the manifest keys of the dynamic imports with `separator`, keyed by their default id.
+async fn keys_by_id(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+    separator: &str,
+) -> Result<HashMap<RcStr, RcStr>> {
+    Ok(dynamic_import_entries
+        .await?
+        .iter()
+        .map(|(origin, dynamic_imports)| async move {
+            let origin_path = &*origin.ident().path().await?;
+            Ok(dynamic_imports
+                .iter()
+                .map(|(import, _)| {
+                    (
+                        dynamic_import_id(origin_path, import),
+                        dynamic_import_key(origin_path, import, separator),
+                    )
+                })
+                .collect::<Vec<_>>())
+        })
+        .try_flat_join()
+        .await?
+        .into_iter()
+        .collect())
+}
+
+/// Returns the entrypoints reaching the origin of the dynamic imports, keyed by the id of the
+/// dynamic import.
+async fn scopes_by_id(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+    scopes: Vc<DynamicImportScopes>,
+) -> Result<HashMap<RcStr, Vec<RcStr>>> {
+    let scopes = &*scopes.await?;
+    Ok(dynamic_import_entries
+        .await?
+        .iter()
+        .map(|(origin, dynamic_imports)| async move {
+            let Some(scope) = scopes.get(origin) else {
+                return Ok(vec![]);
+            };
+            let origin_path = &*origin.ident().path().await?;
+            Ok(dynamic_imports
+                .iter()
+                .map(|(import, _)| (dynamic_import_id(origin_path, import), scope.clone()))
+                .collect())
+        })
+        .try_flat_join()
+        .await?
+        .into_iter()
+        .collect())
+}
+
+async fn loadable_manifest_asset(
+    output_path: Vc<FileSystemPath>,
+    loadable_manifest: &impl Serialize,
+) -> Result<ResolvedVc<Box<dyn OutputAsset>>> {
+    manifest_asset(output_path, manifest_content(loadable_manifest, None)?).await
+}
+
+fn manifest_content(
+    loadable_manifest: &impl Serialize,
+    serializer: Option<ResolvedVc<Box<dyn ManifestSerializer>>>,
+) -> Result<Vc<FileContent>> {
+    Ok(match serializer {
+        Some(serializer) => serializer.serialize(serde_json::to_string(loadable_manifest)?.into()),
+        None => FileContent::Content(File::from(serde_json::to_string_pretty(loadable_manifest)?))
+            .cell(),
+    })
+}
 
-            loadable_manifest.insert(id, manifest_item);
+async fn manifest_asset(
+    output_path: Vc<FileSystemPath>,
+    content: Vc<FileContent>,
+) -> Result<ResolvedVc<Box<dyn OutputAsset>>> {
+    let manifest = VirtualOutputAsset::new(output_path, AssetContent::file(content))
+        .to_resolved()
+        .await?;
+
+    Ok(ResolvedVc::upcast(manifest))
+}
+
+async fn output_asset_content_hash(asset: Vc<Box<dyn OutputAsset>>) -> Result<RcStr> {
+    hash_output_asset_content(asset, content_hash).await
+}
+
+async fn output_asset_integrity(asset: Vc<Box<dyn OutputAsset>>) -> Result<RcStr> {
+    hash_output_asset_content(asset, subresource_integrity).await
+}
+
+async fn hash_output_asset_content(
+    asset: Vc<Box<dyn OutputAsset>>,
+    hash: fn(&[u8]) -> RcStr,
+) -> Result<RcStr> {
+    Ok(match &*asset.content().file_content().await? {
+        FileContent::Content(file) => hash(&file.content().to_bytes()?),
+        FileContent::NotFound => {
+            bail!(
+                "loadable manifest file {} has no content to hash",
+                asset.ident().path().await?.path
+            );
+        }
+    })
+}
+
+/// Hashes file contents into the hex string recorded in `contentHashes`.
+fn content_hash(content: &[u8]) -> RcStr {
+    encode_hex(hash_xxh3_hash64(content)).into()
+}
+
+/// The subresource integrity of file contents, as set on the `integrity` attribute of a `<script>`.
+fn subresource_integrity(content: &[u8]) -> RcStr {
+    format!("sha384-{}", STANDARD.encode(Sha384::digest(content))).into()
+}
+
+fn content_address(content: impl AsRef<[u8]>) -> RcStr {
+    Sha256::digest(content)[..4]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>()
+        .into()
+}
+
+fn shard_index(id: &str, shard_count: u8) -> usize {
+    (hash_xxh3_hash64(id) % u64::from(shard_count)) as usize
+}
+
+/// The violations found by [check_loadable_manifest_shape], empty if the manifest is valid.
+#[turbo_tasks::value(shared)]
+pub struct LoadableManifestShapeReport {
+    pub errors: Vec<RcStr>,
+}
+
+/// Checks an emitted `react-loadable-manifest.json` against the shape read by the Next.js runtime:
+/// an object mapping each id to an entry with a string `id`, a `files` array of paths (or of
+/// objects with a `path` in version 2) and an optional `contentHashes` array of the same length.
+/// Every path must be served from `/_next/`.
+#[turbo_tasks::function]
+pub async fn check_loadable_manifest_shape(
+    manifest_asset: Vc<Box<dyn OutputAsset>>,
+) -> Result<Vc<LoadableManifestShapeReport>> {
+    let FileContent::Content(file) = &*manifest_asset.content().file_content().await? else {
+        bail!(
+            "loadable manifest {} has no content to validate",
+            manifest_asset.ident().path().await?.path
+        );
+    };
+
+    let errors = match serde_json::from_slice(&file.content().to_bytes()?) {
+        Ok(manifest) => validate_loadable_manifest(&manifest),
+        Err(err) => vec![format!("manifest is not valid JSON: {err}").into()],
+    };
+
+    Ok(LoadableManifestShapeReport { errors }.cell())
+}
+
+fn validate_loadable_manifest(manifest: &serde_json::Value) -> Vec<RcStr> {
+    let mut errors = vec![];
+    let Some(entries) = manifest.as_object() else {
+        errors.push("manifest must be an object".into());
+        return errors;
+    };
+
+    for (key, entry) in entries {
+        let Some(entry) = entry.as_object() else {
+            errors.push(format!("{key:?} must be an object").into());
+            continue;
+        };
+
+        match entry.get("id") {
+            Some(serde_json::Value::String(_)) => {}
+            Some(_) => errors.push(format!("{key:?}.id must be a string").into()),
+            None => errors.push(format!("{key:?} is missing the required field id").into()),
+        }
+
+        let files = match entry.get("files") {
+            Some(serde_json::Value::Array(files)) => files,
+            Some(_) => {
+                errors.push(format!("{key:?}.files must be an array").into());
+                continue;
+            }
+            None => {
+                errors.push(format!("{key:?} is missing the required field files").into());
+                continue;
+            }
+        };
+        for (i, file) in files.iter().enumerate() {
+            let path = file
+                .as_str()
+                .or_else(|| file.get("path").and_then(|path| path.as_str()));
+            match path {
+                Some(file) if !file.starts_with("/_next/") => errors.push(
+                    format!("{key:?}.files[{i}] must start with /_next/, got {file:?}").into(),
+                ),
+                Some(_) => {}
+                None => errors.push(
+                    format!("{key:?}.files[{i}] must be a path or an object with a path").into(),
+                ),
+            }
+        }
+
+        match entry.get("contentHashes") {
+            Some(serde_json::Value::Array(content_hashes)) => {
+                if content_hashes.len() != files.len() {
+                    errors
+                        .push(format!("{key:?}.contentHashes must have one hash per file").into());
+                }
+                if !content_hashes.iter().all(|hash| hash.is_string()) {
+                    errors.push(format!("{key:?}.contentHashes must only contain strings").into());
+                }
+            }
+            Some(_) => errors.push(format!("{key:?}.contentHashes must be an array").into()),
+            None => {}
         }
     }
 
-    let loadable_manifest = VirtualOutputAsset::new(
-        output_path,
-        AssetContent::file(
-            FileContent::Content(File::from(serde_json::to_string_pretty(
-                &loadable_manifest,
-            )?))
-            .cell(),
-        ),
-    )
-    .to_resolved()
-    .await?;
+    errors
+}
 
-    output.push(ResolvedVc::upcast(loadable_manifest));
-    Ok(Vc::cell(output))
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use turbo_tasks::TurboTasks;
+    use turbo_tasks_fs::{FileSystem, VirtualFileSystem};
+    use turbo_tasks_memory::MemoryBackend;
+    use turbopack_core::{raw_module::RawModule, virtual_source::VirtualSource};
+
+    use super::*;
+
+    /// The [DynamicImportedChunks] of the module at `origin` of `root` importing `import`, whose
+    /// chunks are `files` in the `out` directory of `root`.
+    async fn dynamic_imported_chunks(
+        root: Vc<FileSystemPath>,
+        origin: &str,
+        import: &str,
+        files: &[&str],
+    ) -> Result<Vc<DynamicImportedChunks>> {
+        let origin = ResolvedVc::upcast(
+            RawModule::new(Vc::upcast(VirtualSource::new(
+                root.join(origin.into()),
+                AssetContent::file(File::from("").into()),
+            )))
+            .to_resolved()
+            .await?,
+        );
+        let chunks = files
+            .iter()
+            .map(|file| async move {
+                Ok(ResolvedVc::upcast(
+                    VirtualOutputAsset::new(
+                        root.join(format!("out/{file}").into()),
+                        AssetContent::file(File::from(format!("/* {file} */")).into()),
+                    )
+                    .to_resolved()
+                    .await?,
+                ))
+            })
+            .try_join()
+            .await?;
+        Ok(Vc::cell(FxIndexMap::from_iter([(
+            origin,
+            vec![(import.into(), ResolvedVc::cell(chunks))],
+        )])))
+    }
+
+    /// The parsed content of the JSON file `name` of `output`.
+    async fn output_json(output: Vc<OutputAssets>, name: &str) -> Result<serde_json::Value> {
+        for asset in output.await?.iter() {
+            if asset.ident().path().await?.file_name() != name {
+                continue;
+            }
+            let FileContent::Content(file) = &*asset.content().file_content().await? else {
+                bail!("{name} has no content");
+            };
+            return Ok(serde_json::from_str(&file.content().to_str()?)?);
+        }
+        bail!("{name} was not emitted")
+    }
+
+    #[test]
+    fn content_hash_is_stable_for_unchanged_content() {
+        let content = b"(globalThis.TURBOPACK = globalThis.TURBOPACK || []).push([]);";
+
+        assert_eq!(content_hash(content), content_hash(content));
+        assert_eq!(content_hash(content).len(), 16);
+        assert_ne!(content_hash(content), content_hash(b"changed"));
+    }
+
+    #[test]
+    fn referenced_files_match_manifest_files() {
+        let chunk_files = vec![
+            (
+                RcStr::from("pages/index.js -> ./a"),
+                vec![
+                    (RcStr::from("static/chunks/a.js"), 1),
+                    (RcStr::from("static/chunks/shared.js"), 2),
+                ],
+            ),
+            (
+                RcStr::from("pages/index.js -> ./b"),
+                vec![(RcStr::from("static/chunks/shared.js"), 2)],
+            ),
+        ];
+
+        let mut manifest_files = chunk_files
+            .iter()
+            .flat_map(|(_, files)| files.iter().map(|(path, _)| path.clone()))
+            .collect::<Vec<_>>();
+        manifest_files.sort();
+        manifest_files.dedup();
+
+        let referenced = referenced_files(&chunk_files);
+        assert_eq!(
+            referenced.keys().cloned().collect::<Vec<_>>(),
+            manifest_files
+        );
+        assert_eq!(referenced.values().copied().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn validates_emitted_manifest() {
+        let manifest = HashMap::from([(
+            RcStr::from("pages/index.js -> ./a"),
+            LoadableManifest {
+                id: "pages/index.js -> ./a".into(),
+                files: LoadableManifestFiles::Paths(vec!["/_next/static/chunks/a.js".into()]),
+                content_hashes: Some(vec![content_hash(b"a")]),
+                retry: None,
+                router: Some(LoadableRouter::Pages),
+                scope: Some(vec!["pages/index.js".into()]),
+                empty: None,
+                overflow: None,
+            },
+        )]);
+
+        assert_eq!(
+            validate_loadable_manifest(&serde_json::to_value(manifest).unwrap()),
+            Vec::<RcStr>::new()
+        );
+    }
+
+    #[test]
+    fn serializes_retry_options() {
+        let entry = LoadableManifest {
+            id: "pages/index.js -> ./flaky".into(),
+            files: LoadableManifestFiles::Paths(vec!["static/chunks/flaky.js".into()]),
+            content_hashes: None,
+            retry: Some(LoadableRetry {
+                max_attempts: Some(3),
+                retriable: Some(true),
+            }),
+            router: Some(LoadableRouter::App),
+            scope: None,
+            empty: None,
+            overflow: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(entry).unwrap(),
+            serde_json::json!({
+                "id": "pages/index.js -> ./flaky",
+                "files": ["static/chunks/flaky.js"],
+                "retry": { "maxAttempts": 3, "retriable": true },
+                "router": "app",
+            })
+        );
+    }
+
+    #[test]
+    fn handles_zero_chunk_entries() {
+        let entry = || LoadableManifest {
+            id: "pages/index.js -> ./shared".into(),
+            files: manifest_files(std::iter::empty(), LoadableManifestVersion::V1),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(EmptyLoadableEntries::Keep.apply(entry())).unwrap(),
+            serde_json::json!({ "id": "pages/index.js -> ./shared", "files": [] })
+        );
+        assert!(EmptyLoadableEntries::Omit.apply(entry()).is_none());
+        assert_eq!(
+            serde_json::to_value(EmptyLoadableEntries::Mark.apply(entry())).unwrap(),
+            serde_json::json!({ "id": "pages/index.js -> ./shared", "files": [], "empty": true })
+        );
+
+        let entry = LoadableManifest {
+            files: manifest_files(
+                std::iter::once("static/chunks/a.js".into()),
+                LoadableManifestVersion::V1,
+            ),
+            ..entry()
+        };
+        assert_eq!(
+            EmptyLoadableEntries::Omit
+                .apply(entry)
+                .map(|entry| entry.empty),
+            Some(None)
+        );
+    }
+
+    #[test]
+    fn records_overflowing_files() {
+        let files = ["a.js", "b.js", "c.js", "d.js"];
+
+        let (kept, overflow) = cap_files(&files, Some(3));
+        assert_eq!(kept, ["a.js", "b.js", "c.js"]);
+        assert_eq!(overflow, Some(1));
+        assert_eq!(cap_files(&files, Some(4)), (&files[..], None));
+        assert_eq!(cap_files(&files, None), (&files[..], None));
+
+        let entry = LoadableManifest {
+            id: "pages/index.js -> ./huge".into(),
+            files: manifest_files(
+                kept.iter().map(|file| RcStr::from(*file)),
+                LoadableManifestVersion::V1,
+            ),
+            overflow,
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(entry).unwrap(),
+            serde_json::json!({
+                "id": "pages/index.js -> ./huge",
+                "files": ["a.js", "b.js", "c.js"],
+                "overflow": 1,
+            })
+        );
+    }
+
+    #[test]
+    fn serializes_files_by_version() {
+        let paths = || ["static/chunks/a.js".into(), "static/chunks/b.js".into()].into_iter();
+
+        assert_eq!(
+            serde_json::to_value(manifest_files(paths(), LoadableManifestVersion::V1)).unwrap(),
+            serde_json::json!(["static/chunks/a.js", "static/chunks/b.js"])
+        );
+
+        let files = manifest_files(paths(), LoadableManifestVersion::V2);
+        assert_eq!(
+            serde_json::to_value(&files).unwrap(),
+            serde_json::json!([
+                { "path": "static/chunks/a.js", "kind": "js", "as": "script" },
+                { "path": "static/chunks/b.js", "kind": "js", "as": "script" },
+            ])
+        );
+        assert_eq!(
+            validate_loadable_manifest(&serde_json::json!({
+                "a": {
+                    "id": "a",
+                    "files": [
+                        { "path": "/_next/static/chunks/a.js", "kind": "js", "as": "script" },
+                    ],
+                },
+            })),
+            Vec::<RcStr>::new()
+        );
+    }
+
+    #[test]
+    fn addresses_files_by_content_hash() {
+        assert_eq!(
+            content_hash_address("cas/", "0123abcd", "static/chunks/a.js"),
+            "cas/0123abcd.js"
+        );
+        assert_eq!(
+            content_hash_address("", "0123abcd", "static/chunks.v2/LICENSE"),
+            "0123abcd"
+        );
+    }
+
+    #[test]
+    fn marks_css_only_entries() {
+        let files = manifest_files(
+            ["static/chunks/theme.css".into()].into_iter(),
+            LoadableManifestVersion::V2,
+        );
+        assert_eq!(
+            serde_json::to_value(&files).unwrap(),
+            serde_json::json!([
+                { "path": "static/chunks/theme.css", "kind": "css", "as": "style" },
+            ])
+        );
+        assert_eq!(manifest_file_kind("static/chunks/a.js.map"), None);
+    }
+
+    #[tokio::test]
+    async fn builds_manifest_for_css_module_imports() {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = Vc::upcast::<Box<dyn FileSystem>>(VirtualFileSystem::new()).root();
+            // A CSS module loads its stylesheet and the JavaScript exporting its class names.
+            let chunks = dynamic_imported_chunks(
+                root,
+                "pages/index.js",
+                "./styles.module.css",
+                &[
+                    "static/chunks/styles_module_css.css",
+                    "static/chunks/styles_module_css.js",
+                ],
+            )
+            .await?;
+            let output = create_react_loadable_manifest(
+                chunks,
+                root.join("out".into()),
+                root.join("out/react-loadable-manifest.json".into()),
+                LoadableManifestOptions {
+                    version: LoadableManifestVersion::V2,
+                    ..Default::default()
+                }
+                .cell(),
+            );
+
+            let manifest = output_json(output, "react-loadable-manifest.json").await?;
+            let id = dynamic_import_id(
+                &*root.join("pages/index.js".into()).await?,
+                "./styles.module.css",
+            );
+            assert_eq!(manifest[&*id]["id"], *id);
+            assert_eq!(
+                manifest[&*id]["files"],
+                serde_json::json!([
+                    {
+                        "path": "static/chunks/styles_module_css.css",
+                        "kind": "css",
+                        "as": "style",
+                    },
+                    {
+                        "path": "static/chunks/styles_module_css.js",
+                        "kind": "js",
+                        "as": "script",
+                    },
+                ])
+            );
+
+            anyhow::Ok(())
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn leaves_out_the_router_by_default() {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = Vc::upcast::<Box<dyn FileSystem>>(VirtualFileSystem::new()).root();
+            let chunks = dynamic_imported_chunks(
+                root,
+                "pages/index.js",
+                "../components/hello",
+                &["static/chunks/hello.js"],
+            )
+            .await?;
+            let output = create_react_loadable_manifest(
+                chunks,
+                root.join("out".into()),
+                root.join("out/react-loadable-manifest.json".into()),
+                LoadableManifestOptions::default().cell(),
+            );
+
+            let id = dynamic_import_id(
+                &*root.join("pages/index.js".into()).await?,
+                "../components/hello",
+            );
+            assert_eq!(
+                output_json(output, "react-loadable-manifest.json").await?,
+                serde_json::json!({
+                    &*id: { "id": &*id, "files": ["static/chunks/hello.js"] },
+                })
+            );
+
+            anyhow::Ok(())
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn emits_integrity_manifest_keyed_like_the_manifest() {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = Vc::upcast::<Box<dyn FileSystem>>(VirtualFileSystem::new()).root();
+            let chunks = dynamic_imported_chunks(
+                root,
+                "pages/index.js",
+                "../components/hello",
+                &["static/chunks/hello.js", "static/chunks/shared.js"],
+            )
+            .await?;
+            let output = create_react_loadable_manifest(
+                chunks,
+                root.join("out".into()),
+                root.join("out/react-loadable-manifest.json".into()),
+                LoadableManifestOptions {
+                    integrity_manifest: true,
+                    ..Default::default()
+                }
+                .cell(),
+            );
+
+            let manifest = output_json(output, "react-loadable-manifest.json").await?;
+            let integrity = output_json(output, "react-loadable-integrity.json").await?;
+            let keys = |json: &serde_json::Value| {
+                let mut keys = json
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                keys.sort();
+                keys
+            };
+            assert_eq!(keys(&integrity), keys(&manifest));
+
+            let id = dynamic_import_id(
+                &*root.join("pages/index.js".into()).await?,
+                "../components/hello",
+            );
+            assert_eq!(
+                integrity[&*id],
+                serde_json::json!({
+                    "static/chunks/hello.js":
+                        &*subresource_integrity(b"/* static/chunks/hello.js */"),
+                    "static/chunks/shared.js":
+                        &*subresource_integrity(b"/* static/chunks/shared.js */"),
+                })
+            );
+
+            anyhow::Ok(())
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn msgpack_manifest_decodes_to_the_json_manifest() {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = Vc::upcast::<Box<dyn FileSystem>>(VirtualFileSystem::new()).root();
+            let chunks = dynamic_imported_chunks(
+                root,
+                "pages/index.js",
+                "../components/hello",
+                &["static/chunks/hello.js"],
+            )
+            .await?;
+            let options = LoadableManifestOptions::default().cell();
+            let output_path = root.join("out/react-loadable-manifest.json".into());
+            let json = create_react_loadable_manifest(
+                chunks,
+                root.join("out".into()),
+                output_path,
+                options,
+            );
+            let msgpack = create_react_loadable_manifest_msgpack(
+                chunks,
+                root.join("out".into()),
+                output_path,
+                options,
+            );
+
+            let FileContent::Content(file) = &*msgpack.content().file_content().await? else {
+                bail!("react-loadable-manifest.json.msgpack has no content");
+            };
+            let decoded: serde_json::Value = rmp_serde::from_slice(&file.content().to_bytes()?)?;
+            assert_eq!(
+                decoded,
+                output_json(json, "react-loadable-manifest.json").await?
+            );
+
+            anyhow::Ok(())
+        })
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn infers_preload_as_by_extension() {
+        for (path, expected) in [
+            ("static/chunks/a.js", Some("script")),
+            ("static/chunks/a.css", Some("style")),
+            ("static/media/module.wasm", Some("fetch")),
+            ("static/media/inter.woff2", Some("font")),
+            ("static/media/inter.woff", Some("font")),
+            ("static/media/logo.png", Some("image")),
+            ("static/chunks/a.js.map", None),
+            ("static/media/LICENSE", None),
+        ] {
+            assert_eq!(preload_as(path).as_deref(), expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn reports_invalid_manifest_entries() {
+        let manifest = serde_json::json!({
+            "hash-count": { "id": "hash-count", "files": ["/_next/a.js"], "contentHashes": [] },
+            "missing-id": { "files": [] },
+            "outside-next": {
+                "id": "outside-next",
+                "files": [
+                    "/_next/static/chunks/a.js",
+                    "static/chunks/b.js",
+                    "/static/chunks/c.js",
+                    1,
+                ],
+            },
+        });
+
+        assert_eq!(
+            validate_loadable_manifest(&manifest),
+            vec![
+                "\"hash-count\".contentHashes must have one hash per file",
+                "\"missing-id\" is missing the required field id",
+                "\"outside-next\".files[1] must start with /_next/, got \"static/chunks/b.js\"",
+                "\"outside-next\".files[2] must start with /_next/, got \"/static/chunks/c.js\"",
+                "\"outside-next\".files[3] must be a path or an object with a path",
+            ]
+        );
+        assert_eq!(
+            validate_loadable_manifest(&serde_json::json!([])),
+            vec!["manifest must be an object"]
+        );
+    }
+
+    #[test]
+    fn reports_progress_per_entry() {
+        struct Counter(Mutex<Vec<(usize, usize)>>);
+
+        impl LoadableManifestProgress for Counter {
+            fn entry(&self, count: usize, total: usize) {
+                self.0.lock().unwrap().push((count, total));
+            }
+        }
+
+        let counter = Counter(Mutex::new(vec![]));
+        let entries = ["a", "b", "c"];
+
+        assert_eq!(
+            with_progress(&entries, Some(&counter)).count(),
+            entries.len()
+        );
+        assert_eq!(*counter.0.lock().unwrap(), vec![(1, 3), (2, 3), (3, 3)]);
+        assert_eq!(with_progress(&entries, None).count(), entries.len());
+    }
+
+    #[test]
+    fn looks_up_files_by_specifier() {
+        let manifest = specifier_manifest(vec![
+            (
+                "[project]/pages/index.js".into(),
+                vec![
+                    (
+                        "./components/Chart".into(),
+                        vec!["static/chunks/chart.js".into()],
+                    ),
+                    ("./hello".into(), vec!["static/chunks/hello.js".into()]),
+                ],
+            ),
+            (
+                "[project]/pages/about.js".into(),
+                vec![(
+                    "./components/Chart".into(),
+                    vec!["static/chunks/chart-about.js".into()],
+                )],
+            ),
+            (
+                "[project]/pages/index.js".into(),
+                vec![("./other".into(), vec!["static/chunks/other.js".into()])],
+            ),
+        ]);
+
+        let index = &manifest["[project]/pages/index.js"];
+        assert_eq!(
+            index["./components/Chart"],
+            vec![RcStr::from("static/chunks/chart.js")]
+        );
+        assert_eq!(
+            index["./other"],
+            vec![RcStr::from("static/chunks/other.js")]
+        );
+        assert_eq!(
+            manifest["[project]/pages/about.js"]["./components/Chart"],
+            vec![RcStr::from("static/chunks/chart-about.js")]
+        );
+    }
+
+    #[test]
+    fn content_address_is_sha256_prefix() {
+        assert_eq!(content_address(""), "e3b0c442");
+        assert_eq!(content_address("{}"), "44136fa3");
+    }
+
+    #[test]
+    fn parses_trace_context_from_traceparent() {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        assert_eq!(
+            TraceContext::from_traceparent(traceparent, Some(0x2a)),
+            Some(TraceContext {
+                trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".into(),
+                span_id: "000000000000002a".into(),
+            })
+        );
+        assert_eq!(
+            TraceContext::from_traceparent(traceparent, None),
+            Some(TraceContext {
+                trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".into(),
+                span_id: "00f067aa0ba902b7".into(),
+            })
+        );
+        for traceparent in [
+            "",
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        ] {
+            assert_eq!(TraceContext::from_traceparent(traceparent, None), None);
+        }
+    }
+
+    #[test]
+    fn serializes_trace_context_next_to_the_entries() {
+        let loadable_manifest = BTreeMap::from([(
+            RcStr::from("pages/index.js -> ./a"),
+            LoadableManifest {
+                id: "pages/index.js -> ./a".into(),
+                files: LoadableManifestFiles::Paths(vec!["static/chunks/a.js".into()]),
+                ..Default::default()
+            },
+        )]);
+        let trace_context = TraceContext {
+            trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".into(),
+            span_id: "00f067aa0ba902b7".into(),
+        };
+
+        assert_eq!(
+            serde_json::to_value(LoadableManifestWithTraceContext {
+                trace_context: Some(&trace_context),
+                loadable_manifest: &loadable_manifest,
+            })
+            .unwrap(),
+            serde_json::json!({
+                "__otel": {
+                    "traceId": "4bf92f3577b34da6a3ce929d0e0e4736",
+                    "spanId": "00f067aa0ba902b7",
+                },
+                "pages/index.js -> ./a": {
+                    "id": "pages/index.js -> ./a",
+                    "files": ["static/chunks/a.js"],
+                },
+            })
+        );
+        // Without a trace context the manifest is unchanged.
+        assert_eq!(
+            serde_json::to_string_pretty(&LoadableManifestWithTraceContext {
+                trace_context: None,
+                loadable_manifest: &loadable_manifest,
+            })
+            .unwrap(),
+            serde_json::to_string_pretty(&loadable_manifest).unwrap()
+        );
+    }
+
+    #[test]
+    fn subresource_integrity_is_base64_sha384() {
+        assert_eq!(
+            subresource_integrity(b""),
+            "sha384-OLBgp1GsljhM2TJ+sbHjaiH9txEUvgdDTAzHv2P24donTt6/529l+9Ua0vFImLlb"
+        );
+        assert_eq!(
+            subresource_integrity(b"alert('Hello, world.');"),
+            "sha384-H8BRh8j48O9oYatfu5AZzq6A9RINhZO5H16dQZngK7T62em8MUt1FLm52t+eX6xO"
+        );
+    }
+
+    #[test]
+    fn shard_index_is_stable_and_in_range() {
+        let id = "[project]/pages/index.js -> ../components/hello";
+
+        assert_eq!(shard_index(id, 8), shard_index(id, 8));
+        for shard_count in 1..=16 {
+            assert!(shard_index(id, shard_count) < usize::from(shard_count));
+        }
+        assert_eq!(shard_index(id, 1), 0);
+    }
 }
@@ -18,6 +18,7 @@ use next_core::{
 use petgraph::{
     graph::{DiGraph, NodeIndex},
     visit::{Dfs, VisitMap, Visitable},
+    Direction,
 };
 use serde::{Deserialize, Serialize};
 use tracing::Instrument;
@@ -39,7 +40,7 @@ use turbopack_core::{
 
 use crate::{
     client_references::{map_client_references, ClientReferenceMapType, ClientReferencesSet},
-    dynamic_imports::{map_next_dynamic, DynamicImports},
+    dynamic_imports::{map_next_dynamic, scopes_by_module, DynamicImportScopes, DynamicImports},
     project::Project,
     server_actions::{map_server_actions, to_rsc_context, AllActions, AllModuleActions},
 };
@@ -329,6 +330,11 @@ impl SingleModuleGraph {
             .context("Couldn't find entry module in graph")
     }
 
+    /// Iterate over the entry modules of the graph, in no particular order.
+    pub fn iter_entries(&self) -> impl Iterator<Item = ResolvedVc<Box<dyn Module>>> + '_ {
+        self.entries.keys().copied()
+    }
+
     /// Iterate over all nodes in the graph (potentially in the whole app!).
     pub fn iter_nodes(&self) -> impl Iterator<Item = &'_ SingleModuleGraphNode> + '_ {
         self.graph.node_weights()
@@ -343,6 +349,26 @@ impl SingleModuleGraph {
             .map(move |idx| (idx, self.graph.node_weight(idx).unwrap()))
     }
 
+    /// Returns the given modules together with all modules in the graph directly referencing them.
+    pub fn with_direct_importers(
+        &self,
+        modules: &[ResolvedVc<Box<dyn Module>>],
+    ) -> HashSet<ResolvedVc<Box<dyn Module>>> {
+        let modules: HashSet<_> = modules.iter().copied().collect();
+        let mut result = HashSet::new();
+        for (idx, node) in self.enumerate_nodes() {
+            if modules.contains(&node.module) {
+                result.insert(node.module);
+                result.extend(
+                    self.graph
+                        .neighbors_directed(idx, Direction::Incoming)
+                        .map(|importer| self.graph.node_weight(importer).unwrap().module),
+                );
+            }
+        }
+        result
+    }
+
     /// Traverses all reachable nodes (once)
     pub fn traverse_from_entry<'a>(
         &'a self,
@@ -599,6 +625,9 @@ impl NextDynamicGraph {
         .cell())
     }
 
+    /// Returns the dynamic imports of all modules reachable from `entry`. These include the
+    /// modules it imports statically, so the `dynamic()` calls of e.g. a route config shared by
+    /// several pages are attributed to each of them.
     #[turbo_tasks::function]
     pub async fn get_next_dynamic_imports_for_endpoint(
         &self,
@@ -626,6 +655,39 @@ impl NextDynamicGraph {
         .instrument(span)
         .await
     }
+
+    /// Returns the entries of the graph reaching each module with dynamic imports, see
+    /// [DynamicImportScopes].
+    #[turbo_tasks::function]
+    pub async fn get_next_dynamic_import_scopes(&self) -> Result<Vc<DynamicImportScopes>> {
+        let span = tracing::info_span!("collect next/dynamic import scopes");
+        async move {
+            let graph = &*self.graph.await?;
+            let data = &self.data.await?;
+
+            // Sorted, as the entries of the graph are unordered.
+            let mut entries = graph
+                .iter_entries()
+                .map(|entry| async move { Ok((entry.ident().path().await?.path.clone(), entry)) })
+                .try_join()
+                .await?;
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            let mut reached_by_entry = Vec::with_capacity(entries.len());
+            for (entry_path, entry) in entries {
+                let mut reached = vec![];
+                graph.traverse_from_entry(entry, |node| {
+                    if data.contains_key(&node.module) {
+                        reached.push(node.module);
+                    }
+                })?;
+                reached_by_entry.push((entry_path, reached));
+            }
+            Ok(Vc::cell(scopes_by_module(reached_by_entry)))
+        }
+        .instrument(span)
+        .await
+    }
 }
 
 #[turbo_tasks::value]
@@ -892,6 +954,34 @@ impl ReducedGraphs {
         .await
     }
 
+    /// Returns the entrypoints reaching each RSC and SSR module with dynamic imports, to scope the
+    /// manifest entries of the dynamic imports to the routes using them.
+    #[turbo_tasks::function]
+    pub async fn get_next_dynamic_import_scopes(&self) -> Result<Vc<DynamicImportScopes>> {
+        if let [graph] = &self.next_dynamic[..] {
+            return Ok(graph.get_next_dynamic_import_scopes());
+        }
+
+        let mut result: FxIndexMap<ResolvedVc<Box<dyn Module>>, Vec<RcStr>> = FxIndexMap::default();
+        for scopes in self
+            .next_dynamic
+            .iter()
+            .map(|graph| async move { graph.get_next_dynamic_import_scopes().await })
+            .try_join()
+            .await?
+        {
+            for (module, entries) in scopes.iter() {
+                let scope = result.entry(*module).or_default();
+                for entry in entries {
+                    if !scope.contains(entry) {
+                        scope.push(entry.clone());
+                    }
+                }
+            }
+        }
+        Ok(Vc::cell(result))
+    }
+
     /// Returns the server actions for the given page.
     #[turbo_tasks::function]
     pub async fn get_server_actions_for_endpoint(
@@ -66,7 +66,7 @@ use turbopack_nodejs::NodeJsChunkingContext;
 use crate::{
     dynamic_imports::{collect_chunk_group, collect_evaluated_chunk_group, DynamicImportedChunks},
     font::create_font_manifest,
-    loadable_manifest::create_react_loadable_manifest,
+    loadable_manifest::{create_react_loadable_manifest, LoadableManifestOptions},
     module_graph::get_reduced_graphs_for_endpoint,
     nft_json::NftJsonAsset,
     paths::{
@@ -75,7 +75,7 @@ use crate::{
     },
     project::Project,
     route::{Endpoint, Route, Routes, WrittenEndpoint},
-    webpack_stats::generate_webpack_stats,
+    webpack_stats::{dynamic_imports_webpack_stats, generate_webpack_stats},
 };
 
 #[turbo_tasks::value]
@@ -1059,6 +1059,7 @@ impl PageEndpoint {
             node_root.join(
                 format!("server/pages{loadable_path_prefix}/react-loadable-manifest.json").into(),
             ),
+            LoadableManifestOptions::default().cell(),
         ))
     }
 
@@ -1172,6 +1173,12 @@ impl PageEndpoint {
                     let loadable_manifest_output =
                         self.react_loadable_manifest(*dynamic_import_entries);
                     server_assets.extend(loadable_manifest_output.await?.iter().copied());
+                    let dynamic_imports_webpack_stats = dynamic_imports_webpack_stats(
+                        this.pages_project.project(),
+                        *dynamic_import_entries,
+                        node_root.join(format!("server/pages{manifest_path_prefix}").into()),
+                    );
+                    server_assets.extend(dynamic_imports_webpack_stats.await?.iter().copied());
                 }
 
                 PageEndpointOutput::NodeJs {
@@ -1265,6 +1272,12 @@ impl PageEndpoint {
                 let loadable_manifest_output =
                     self.react_loadable_manifest(*dynamic_import_entries);
                 server_assets.extend(loadable_manifest_output.await?.iter().copied());
+                let dynamic_imports_webpack_stats = dynamic_imports_webpack_stats(
+                    this.pages_project.project(),
+                    *dynamic_import_entries,
+                    node_root.join(format!("server/pages{manifest_path_prefix}").into()),
+                );
+                server_assets.extend(dynamic_imports_webpack_stats.await?.iter().copied());
 
                 PageEndpointOutput::Edge {
                     files,
@@ -1,11 +1,19 @@
 use anyhow::Result;
 use serde::Serialize;
 use turbo_rcstr::RcStr;
-use turbo_tasks::{FxIndexMap, FxIndexSet, ResolvedVc, Vc};
+use turbo_tasks::{FxIndexMap, FxIndexSet, ResolvedVc, TryJoinIterExt, Vc};
+use turbo_tasks_fs::{File, FileContent, FileSystemPath};
 use turbopack_browser::ecmascript::EcmascriptDevChunk;
 use turbopack_core::{
+    asset::AssetContent,
     chunk::{Chunk, ChunkItem},
-    output::OutputAsset,
+    output::{OutputAsset, OutputAssets},
+    virtual_output::VirtualOutputAsset,
+};
+
+use crate::{
+    dynamic_imports::{resolve_dynamic_imported_chunk_files, DynamicImportedChunks},
+    project::Project,
 };
 
 pub async fn generate_webpack_stats<'a, I>(
@@ -88,6 +96,117 @@ where
     })
 }
 
+/// Emits a webpack 5 `stats.json` fragment of the dynamic imports only, so bundle analysis tools
+/// such as `webpack-bundle-analyzer` can inspect what they load. Every file is an asset and a
+/// chunk, and every dynamic import is a named chunk group of its files, named by its id.
+#[turbo_tasks::function]
+pub async fn convert_dynamic_chunks_to_webpack_stats_partial(
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+    client_relative_path: Vc<FileSystemPath>,
+    output_path: Vc<FileSystemPath>,
+) -> Result<Vc<Box<dyn OutputAsset>>> {
+    let chunk_files =
+        resolve_dynamic_imported_chunk_files(dynamic_import_entries, client_relative_path).await?;
+    let chunk_files = chunk_files
+        .iter()
+        .map(|(id, files)| async move {
+            let files = files
+                .iter()
+                .map(|(path, file)| async move { Ok((path.clone(), *file.size_bytes().await?)) })
+                .try_join()
+                .await?;
+            Ok((id.clone(), files))
+        })
+        .try_join()
+        .await?;
+
+    Ok(Vc::upcast(VirtualOutputAsset::new(
+        output_path,
+        AssetContent::file(
+            FileContent::Content(File::from(serde_json::to_string_pretty(
+                &dynamic_chunks_webpack_stats(&chunk_files),
+            )?))
+            .cell(),
+        ),
+    )))
+}
+
+/// Emits the `webpack-stats-dynamic.json` of the dynamic imports of an endpoint in its server
+/// output directory `output_dir`, see [convert_dynamic_chunks_to_webpack_stats_partial]. Nothing is
+/// emitted unless webpack stats are requested.
+#[turbo_tasks::function]
+pub async fn dynamic_imports_webpack_stats(
+    project: Vc<Project>,
+    dynamic_import_entries: Vc<DynamicImportedChunks>,
+    output_dir: Vc<FileSystemPath>,
+) -> Result<Vc<OutputAssets>> {
+    if !*project.should_create_webpack_stats().await? {
+        return Ok(OutputAssets::empty());
+    }
+    let stats_output = convert_dynamic_chunks_to_webpack_stats_partial(
+        dynamic_import_entries,
+        project.client_relative_path(),
+        output_dir.join("webpack-stats-dynamic.json".into()),
+    )
+    .to_resolved()
+    .await?;
+    Ok(Vc::cell(vec![stats_output]))
+}
+
+fn dynamic_chunks_webpack_stats(
+    chunk_files: &[(RcStr, Vec<(RcStr, Option<u64>)>)],
+) -> WebpackStatsPartial {
+    let mut assets: FxIndexMap<RcStr, WebpackStatsAsset> = FxIndexMap::default();
+    let mut chunks: FxIndexMap<RcStr, WebpackStatsChunk> = FxIndexMap::default();
+    let mut named_chunk_groups = FxIndexMap::default();
+    for (id, files) in chunk_files {
+        // Like in [generate_webpack_stats], files without a known size are left out.
+        let files = files
+            .iter()
+            .filter_map(|(path, size)| Some((path, (*size)?)))
+            .collect::<Vec<_>>();
+        for &(path, size) in &files {
+            assets
+                .entry(path.clone())
+                .or_insert_with(|| WebpackStatsAsset {
+                    ty: "asset".into(),
+                    name: path.clone(),
+                    chunks: vec![path.clone()],
+                    size,
+                    ..Default::default()
+                });
+            chunks
+                .entry(path.clone())
+                .or_insert_with(|| WebpackStatsChunk {
+                    rendered: true,
+                    id: path.clone(),
+                    size,
+                    files: vec![path.clone()],
+                    ..Default::default()
+                });
+        }
+        named_chunk_groups.insert(
+            id.clone(),
+            WebpackStatsEntrypoint {
+                name: id.clone(),
+                chunks: files.iter().map(|(path, _)| (*path).clone()).collect(),
+                assets: files
+                    .iter()
+                    .map(|(path, _)| WebpackStatsEntrypointAssets {
+                        name: (*path).clone(),
+                    })
+                    .collect(),
+            },
+        );
+    }
+
+    WebpackStatsPartial {
+        assets: assets.into_values().collect(),
+        chunks: chunks.into_values().collect(),
+        named_chunk_groups,
+    }
+}
+
 fn normalize_client_path(path: &str) -> String {
     let next_re = regex::Regex::new(r"^_next/").unwrap();
     next_re.replace(path, ".next/").into()
@@ -155,3 +274,69 @@ pub struct WebpackStats {
     pub chunks: Vec<WebpackStatsChunk>,
     pub modules: Vec<WebpackStatsModule>,
 }
+
+/// The part of [WebpackStats] describing the dynamic imports, see
+/// [convert_dynamic_chunks_to_webpack_stats_partial].
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WebpackStatsPartial {
+    pub assets: Vec<WebpackStatsAsset>,
+    pub chunks: Vec<WebpackStatsChunk>,
+    pub named_chunk_groups: FxIndexMap<RcStr, WebpackStatsEntrypoint>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_dynamic_imports_as_named_chunk_groups() {
+        let stats = serde_json::to_value(dynamic_chunks_webpack_stats(&[
+            (
+                "[project]/pages/index.js -> ../components/chart".into(),
+                vec![
+                    ("static/chunks/chart.js".into(), Some(120)),
+                    ("static/chunks/shared.js".into(), Some(40)),
+                ],
+            ),
+            (
+                "[project]/pages/index.js -> ../components/table".into(),
+                vec![
+                    ("static/chunks/table.js".into(), Some(80)),
+                    ("static/chunks/shared.js".into(), Some(40)),
+                    ("static/chunks/unknown.js".into(), None),
+                ],
+            ),
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            stats["assets"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|asset| (
+                    asset["name"].as_str().unwrap(),
+                    asset["size"].as_u64().unwrap()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("static/chunks/chart.js", 120),
+                ("static/chunks/shared.js", 40),
+                ("static/chunks/table.js", 80),
+            ]
+        );
+        assert_eq!(stats["chunks"].as_array().unwrap().len(), 3);
+        assert_eq!(
+            stats["namedChunkGroups"]["[project]/pages/index.js -> ../components/table"],
+            serde_json::json!({
+                "name": "[project]/pages/index.js -> ../components/table",
+                "chunks": ["static/chunks/table.js", "static/chunks/shared.js"],
+                "assets": [
+                    { "name": "static/chunks/table.js" },
+                    { "name": "static/chunks/shared.js" },
+                ],
+            })
+        );
+    }
+}
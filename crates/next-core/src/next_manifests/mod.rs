@@ -274,12 +274,95 @@ pub struct AppPathsManifest {
 // A struct represent a single entry in react-loadable-manifest.json.
 // The manifest is in a format of:
 // { [`${origin} -> ${imported}`]: { id: `${origin} -> ${imported}`, files:
-// string[] } }
+// string[] | ManifestFile[] } }
+// where `files` are paths in version 1 and [ManifestFile]s in version 2, see
+// [LoadableManifestFiles].
 #[derive(Serialize, Default, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct LoadableManifest {
     pub id: RcStr,
-    pub files: Vec<RcStr>,
+    pub files: LoadableManifestFiles,
+    /// Content hashes of `files`, in the same order. Only emitted when requested, for runtimes
+    /// that identify chunks by content rather than by path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_hashes: Option<Vec<RcStr>>,
+    /// How the runtime retries failed loads of `files`, from the `retry` option of `dynamic()`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry: Option<LoadableRetry>,
+    /// The router of the module containing the dynamic import, so hybrid apps can tell the
+    /// entries of both routers apart. Only emitted when requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub router: Option<LoadableRouter>,
+    /// The entrypoints reaching the module containing the dynamic import, so the runtime can
+    /// preload it on these routes only. Only emitted when requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<Vec<RcStr>>,
+    /// Set on entries without `files`, whose module is already part of the chunks loading it,
+    /// when requested to mark them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub empty: Option<bool>,
+    /// The number of files left out of `files` because the entry had more than the configured
+    /// maximum.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overflow: Option<usize>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LoadableRouter {
+    App,
+    Pages,
+}
+
+/// The files of a [LoadableManifest] entry: their paths in version 1 of the manifest, the default
+/// read by the runtime, and [ManifestFile]s in version 2.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum LoadableManifestFiles {
+    Paths(Vec<RcStr>),
+    Files(Vec<ManifestFile>),
+}
+
+impl LoadableManifestFiles {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            LoadableManifestFiles::Paths(paths) => paths.is_empty(),
+            LoadableManifestFiles::Files(files) => files.is_empty(),
+        }
+    }
+}
+
+impl Default for LoadableManifestFiles {
+    fn default() -> Self {
+        LoadableManifestFiles::Paths(vec![])
+    }
+}
+
+/// A file of a version 2 [LoadableManifest] entry. All metadata besides the path is optional.
+#[derive(Serialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestFile {
+    pub path: RcStr,
+    /// `js` or `css`, by the extension of the file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<RcStr>,
+    /// The `as` attribute of a `<link rel="preload">` for the file, by its extension, e.g. `font`
+    /// for `.woff2` or `fetch` for `.wasm`.
+    #[serde(rename = "as", skip_serializing_if = "Option::is_none")]
+    pub preload_as: Option<RcStr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<RcStr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+#[derive(Serialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadableRetry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retriable: Option<bool>,
 }
 
 #[derive(Serialize, Default, Debug)]
@@ -0,0 +1,12 @@
+'use client'
+
+import { useState } from 'react'
+
+export default function ClientThing() {
+  const [clicks, setClicks] = useState(0)
+  return (
+    <button id="client-thing" onClick={() => setClicks(clicks + 1)}>
+      client thing clicked {clicks} times
+    </button>
+  )
+}
@@ -0,0 +1,7 @@
+import dynamic from 'next/dynamic'
+
+const ClientThing = dynamic(() => import('./client-thing'))
+
+export default async function Page() {
+  return <ClientThing />
+}
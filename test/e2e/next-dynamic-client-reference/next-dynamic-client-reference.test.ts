@@ -0,0 +1,38 @@
+import { nextTestSetup } from 'e2e-utils'
+;(process.env.TURBOPACK ? describe : describe.skip)(
+  'next/dynamic of a client component in a server component',
+  () => {
+    const { next, isNextStart } = nextTestSetup({
+      files: __dirname,
+    })
+
+    it('should render the client component', async () => {
+      const $ = await next.render$('/')
+      expect($('#client-thing').text()).toBe('client thing clicked 0 times')
+    })
+
+    if (isNextStart) {
+      it('should add the chunks of the client module to the loadable manifest', async () => {
+        const loadableManifest = JSON.parse(
+          await next.readFile(
+            '.next/server/app/page/react-loadable-manifest.json'
+          )
+        )
+        const entry = Object.entries<{ files: string[] }>(
+          loadableManifest
+        ).find(([id]) => id.endsWith('app/page.js -> ./client-thing'))
+        expect(entry).toBeDefined()
+
+        const files = entry[1].files.filter((file) => file.endsWith('.js'))
+        expect(files.length).toBeGreaterThan(0)
+        const contents = await Promise.all(
+          files.map((file) => next.readFile(`.next/${file}`))
+        )
+        // The client module is chunked, not the client reference of the server.
+        expect(
+          contents.some((content) => content.includes('client thing clicked'))
+        ).toBe(true)
+      })
+    }
+  }
+)
@@ -0,0 +1,3 @@
+export default function Hello() {
+  return <p id="hello">hello from a compiled module</p>
+}
@@ -0,0 +1,25 @@
+import { nextTestSetup } from 'e2e-utils'
+;(process.env.TURBOPACK ? describe : describe.skip)(
+  'next/dynamic in a module compiled by a custom loader',
+  () => {
+    const { next, isNextStart } = nextTestSetup({
+      files: __dirname,
+    })
+
+    it('should render the dynamically imported component', async () => {
+      const $ = await next.render$('/')
+      expect($('#hello').text()).toBe('hello from a compiled module')
+    })
+
+    if (isNextStart) {
+      it('should collect the dynamic import of the compiled module', async () => {
+        const loadableManifest = JSON.parse(
+          await next.readFile('.next/react-loadable-manifest.json')
+        )
+        expect(Object.keys(loadableManifest)).toContainEqual(
+          expect.stringMatching(/components\/hello\.widget -> \.\/hello$/)
+        )
+      })
+    }
+  }
+)
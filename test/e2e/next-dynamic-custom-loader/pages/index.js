@@ -0,0 +1,5 @@
+import Widget from '../components/hello.widget'
+
+export default function Page() {
+  return <Widget />
+}
@@ -0,0 +1,3 @@
+export default function Spinner() {
+  return <p id="spinner">loading...</p>
+}
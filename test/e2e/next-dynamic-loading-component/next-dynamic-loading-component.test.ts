@@ -0,0 +1,21 @@
+import { nextTestSetup } from 'e2e-utils'
+import { retry } from 'next-test-utils'
+
+describe('next/dynamic with a loading component', () => {
+  const { next } = nextTestSetup({
+    files: __dirname,
+  })
+
+  it('should render the loading component on the server', async () => {
+    const $ = await next.render$('/')
+    expect($('#spinner').text()).toBe('loading...')
+    expect($('#chart').length).toBe(0)
+  })
+
+  it('should replace the loading component once loaded', async () => {
+    const browser = await next.browser('/')
+    await retry(async () => {
+      expect(await browser.elementByCss('#chart').text()).toBe('chart')
+    })
+  })
+})
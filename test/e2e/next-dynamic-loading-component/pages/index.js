@@ -0,0 +1,11 @@
+import dynamic from 'next/dynamic'
+import Spinner from '../components/spinner'
+
+const Chart = dynamic(() => import('../components/chart'), {
+  ssr: false,
+  loading: () => <Spinner />,
+})
+
+export default function Page() {
+  return <Chart />
+}
@@ -0,0 +1,10 @@
+import type { Ref } from 'react'
+
+export type FieldProps = {
+  name: string
+  inputRef?: Ref<HTMLInputElement>
+}
+
+export default function Field({ name, inputRef }: FieldProps) {
+  return <input id={name} name={name} ref={inputRef} />
+}
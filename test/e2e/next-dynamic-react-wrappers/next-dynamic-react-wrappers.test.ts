@@ -0,0 +1,26 @@
+import { nextTestSetup } from 'e2e-utils'
+import { retry } from 'next-test-utils'
+
+describe('next/dynamic wrapped in React.memo and forwardRef', () => {
+  const { next } = nextTestSetup({
+    files: __dirname,
+    dependencies: {
+      typescript: 'latest',
+      '@types/react': 'latest',
+    },
+  })
+
+  it('should render the component wrapped in memo', async () => {
+    const browser = await next.browser('/')
+    await retry(async () => {
+      expect(await browser.elementByCss('#hello').text()).toBe('hello')
+    })
+  })
+
+  it('should render the component wrapped in forwardRef with type arguments', async () => {
+    const browser = await next.browser('/')
+    await retry(async () => {
+      expect(await browser.hasElementByCssSelector('#email')).toBe(true)
+    })
+  })
+})
@@ -0,0 +1,20 @@
+import { forwardRef, memo } from 'react'
+import dynamic from 'next/dynamic'
+import type { FieldProps } from '../components/field'
+
+const Hello = memo(dynamic(() => import('../components/hello')))
+
+const Field = dynamic<FieldProps>(() => import('../components/field'))
+
+const Input = forwardRef<HTMLInputElement, Omit<FieldProps, 'inputRef'>>(
+  (props, ref) => <Field {...props} inputRef={ref} />
+)
+
+export default function Page() {
+  return (
+    <>
+      <Hello />
+      <Input name="email" />
+    </>
+  )
+}
@@ -0,0 +1,5 @@
+import dynamic from 'next/dynamic'
+
+export const routes = {
+  chart: dynamic(() => import('../components/chart')),
+}
@@ -0,0 +1,40 @@
+import { nextTestSetup } from 'e2e-utils'
+
+describe('next/dynamic in a shared config module', () => {
+  const { next, isNextStart } = nextTestSetup({
+    files: __dirname,
+  })
+
+  it.each(['/a', '/b'])(
+    'should render the dynamic import of the config on %s',
+    async (page) => {
+      const $ = await next.render$(page)
+      expect($('#chart').text()).toBe('chart')
+    }
+  )
+
+  if (isNextStart) {
+    it.each(['/a', '/b'])(
+      'should preload the chunks of the dynamic import on %s',
+      async (page) => {
+        const loadableManifest = JSON.parse(
+          await next.readFile('.next/react-loadable-manifest.json')
+        )
+        const entry = Object.entries<{ files: string[] }>(
+          loadableManifest
+        ).find(([id]) => id.endsWith('config/routes.js -> ../components/chart'))
+        expect(entry).toBeDefined()
+
+        const $ = await next.render$(page)
+        const scripts = $('script[src]')
+          .map((_, script) => $(script).attr('src'))
+          .get()
+        for (const file of entry[1].files.filter((file) =>
+          file.endsWith('.js')
+        )) {
+          expect(scripts).toContain(`/_next/${file}`)
+        }
+      }
+    )
+  }
+})
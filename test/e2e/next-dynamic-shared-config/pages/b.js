@@ -0,0 +1,6 @@
+import { routes } from '../config/routes'
+
+export default function Page() {
+  const Chart = routes.chart
+  return <Chart />
+}
@@ -0,0 +1,11 @@
+import dynamic from 'next/dynamic'
+
+const Hello = dynamic(() => import('components/hello'))
+
+export default function DynamicPage() {
+  return (
+    <div>
+      <Hello />
+    </div>
+  )
+}
@@ -44,6 +44,11 @@ describe('jsconfig.json baseurl', () => {
       expect($('body').text()).toMatch(/World/)
     })
 
+    it('should render a dynamic import relative to the base url', async () => {
+      const $ = await get$('/dynamic')
+      expect($('body').text()).toMatch(/Hello/)
+    })
+
     it('should have correct module not found error', async () => {
       const basicPage = join(appDir, 'pages/hello.js')
       const contents = await fs.readFile(basicPage, 'utf8')
@@ -87,6 +92,17 @@ describe('jsconfig.json baseurl', () => {
             helloTrace.files.some((file) => file.includes('react/index.js'))
           ).toBe(true)
         })
+
+        it('should add dynamic imports relative to the base url to the loadable manifest', async () => {
+          const loadableManifest = await fs.readJSON(
+            join(appDir, '.next/react-loadable-manifest.json')
+          )
+          expect(
+            Object.keys(loadableManifest).some((id) =>
+              id.endsWith('-> components/hello')
+            )
+          ).toBe(true)
+        })
       }
     )
   })
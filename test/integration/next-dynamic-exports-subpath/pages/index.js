@@ -0,0 +1,11 @@
+import dynamic from 'next/dynamic'
+
+const Feature = dynamic(() => import('exports-pkg/feature'))
+
+export default function Page() {
+  return (
+    <div id="feature">
+      <Feature />
+    </div>
+  )
+}
@@ -0,0 +1,60 @@
+/* eslint-env jest */
+
+import fs from 'fs-extra'
+import { join } from 'path'
+import cheerio from 'cheerio'
+import {
+  renderViaHTTP,
+  findPort,
+  launchApp,
+  killApp,
+  nextBuild,
+} from 'next-test-utils'
+
+const appDir = join(__dirname, '..')
+let appPort
+let app
+
+describe('next/dynamic of a package.json exports subpath', () => {
+  describe('development mode', () => {
+    beforeAll(async () => {
+      appPort = await findPort()
+      app = await launchApp(appDir, appPort)
+    })
+    afterAll(() => killApp(app))
+
+    it('should render the server target of the subpath', async () => {
+      const $ = cheerio.load(await renderViaHTTP(appPort, '/'))
+      expect($('#feature').text()).toBe('feature-default')
+    })
+  })
+  ;(process.env.TURBOPACK_DEV ? describe.skip : describe)(
+    'production mode',
+    () => {
+      beforeAll(async () => {
+        await nextBuild(appDir)
+      })
+
+      it('should add the browser target of the subpath to the loadable manifest', async () => {
+        const loadableManifest = await fs.readJSON(
+          join(appDir, '.next/react-loadable-manifest.json')
+        )
+        const entry = Object.entries(loadableManifest).find(([id]) =>
+          id.endsWith('-> exports-pkg/feature')
+        )
+        expect(entry).toBeDefined()
+
+        const files = entry[1].files.filter((file) => file.endsWith('.js'))
+        expect(files.length).toBeGreaterThan(0)
+        const contents = await Promise.all(
+          files.map((file) =>
+            fs.readFile(join(appDir, '.next', file), 'utf8')
+          )
+        )
+        expect(
+          contents.some((content) => content.includes('feature-browser'))
+        ).toBe(true)
+      })
+    }
+  )
+})